@@ -9,23 +9,162 @@
 //! [reset]: struct.Counter.html#method.reset
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Balance, Promise};
-use near_sdk::collections::{ UnorderedMap};
+use near_sdk::{assert_one_yocto, env, near_bindgen, AccountId, PanicOnDefault, Balance, Promise};
+use near_sdk::collections::{ LookupMap, TreeMap, UnorderedMap, UnorderedSet};
 //use near_sdk::json_types::{U128};
 use serde::Serialize;
 use serde::Deserialize;
-use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+use near_sdk::serde_json::{json, Value};
+use std::collections::HashMap;
 //use near_sdk::env::is_valid_account_id;
 
 near_sdk::setup_alloc!();
 
 pub const VAULT_FEE: u128 = 500;
+pub const RENEWAL_DISCOUNT: u128 = 3000;
+pub const MAX_TAGS: usize = 10;
+pub const MAX_TAG_LENGTH: usize = 32;
+pub const MAX_SEARCH_TERMS: usize = 8;
+pub const MIN_REVIEW_SCORE: i8 = 1;
+pub const MAX_REVIEW_SCORE: i8 = 5;
+pub const MAX_REVIEW_LENGTH: usize = 1000;
+// Ratings are exposed in thousandths of a star
+pub const RATING_PRECISION: u64 = 1000;
+// Bayesian prior for the top rated view: every course starts as if it had this many 3 star reviews
+pub const RATING_PRIOR_MEAN: u64 = 3;
+pub const RATING_PRIOR_WEIGHT: u64 = 5;
+pub const DEFAULT_UPGRADE_DELAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+pub const GAS_FOR_MIGRATE: u64 = 100_000_000_000_000;
+pub const STOPWORDS: [&str; 32] = [
+    "a", "an", "and", "are", "as", "at", "be", "by", "de", "el", "en", "for", "from", "how", "in", "is",
+    "it", "la", "los", "of", "on", "or", "para", "por", "that", "the", "this", "to", "un", "una", "with", "y",
+];
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct CoursePurchased {
     course_id: i128,
     pass_certification: bool,
+    certification_expires_at: Option<u64>,
+    certification_revoked: bool,
+    certified_version: Option<u32>,
+    purchase_price: Balance,
+    purchased_at: u64,
+    certified_at: Option<u64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CertificationStatus {
+    NotCertified,
+    Valid,
+    Expired,
+    Revoked,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CertificationView {
+    user_id: AccountId,
+    course_id: i128,
+    status: CertificationStatus,
+    expires_at: Option<U64>,
+    certified_version: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ActivityKind {
+    Enrolled,
+    Certified,
+    Reviewed,
+    ReviewEdited,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActivityEntry {
+    kind: ActivityKind,
+    course_id: i128,
+    timestamp: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EnrollmentEntry {
+    user_id: AccountId,
+    purchased_at: U64,
+    certified_at: Option<U64>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PauseScope {
+    All,
+    Purchases,
+    Publishing,
+    Reviews,
+    Certification,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseStatus {
+    all: bool,
+    purchases: bool,
+    publishing: bool,
+    reviews: bool,
+    certification: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StagedUpgrade {
+    code_hash: String,
+    staged_at: u64,
+    available_at: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StagedDelay {
+    delay_ns: u64,
+    available_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpgradeDelayView {
+    delay_ns: U64,
+    available_at: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpgradeView {
+    code_hash: String,
+    staged_at: U64,
+    available_at: U64,
+}
+
+// NEP-145 storage balance of an account, in yoctoNEAR
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    total: U128,
+    available: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    min: U128,
+    max: Option<U128>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageAccount {
+    deposit: Balance,
+    used_bytes: u64,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -48,6 +187,67 @@ pub struct CategoriesJson {
     id: i128,
 	name: String,
     img: String,
+    parent_id: Option<i128>,
+    order: u32,
+    slug: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CategoryNode {
+    category: CategoriesJson,
+    children: Vec<CategoryNode>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum LessonType {
+    Video { duration_seconds: u32 },
+    Text { reading_minutes: u32 },
+    Quiz { questions: u32, passing_score: u8 },
+    Assignment { max_score: u32 },
+    Download { size_bytes: U64 },
+    LiveSession { starts_at: U64, duration_seconds: u32 },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ContentStorage {
+    Ipfs,
+    Arweave,
+}
+
+// Lesson bodies live off-chain (usually encrypted), the contract only keeps where to find them
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContentReference {
+    storage: ContentStorage,
+    locator: String, // IPFS CID or Arweave transaction id
+    content_hash: String, // hex sha256 of the stored bytes
+    media_type: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MediaObject {
+    url: String,
+    sha256: String,
+    media_type: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeyEnvelope {
+    learner_public_key: String,
+    encrypted_key: String,
+    payer_id: AccountId,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeyEnvelopeInput {
+    user_id: AccountId,
+    encrypted_key: String,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -55,15 +255,59 @@ pub struct CategoriesJson {
 pub struct TemplateObject {
 	title: String,
     description: String,
-    content: String,
-    tipo: i8, // 1 Video, 2 Text
+    content: ContentReference,
+    lesson_type: LessonType,
+    free_preview: bool,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct TemplateView {
 	title: String,
-    tipo: i8, // 1 Video, 2 Text
+    lesson_type: LessonType,
+    free_preview: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SectionObject {
+    title: String,
+    lessons: Vec<TemplateObject>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SectionView {
+    title: String,
+    lessons: Vec<TemplateView>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContentVersion {
+    version: u32,
+    created_at: u64,
+    changelog: String,
+    img: MediaObject,
+    content: Vec<SectionObject>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContentVersionView {
+    version: u32,
+    created_at: U64,
+    changelog: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CourseStatus {
+    Draft,
+    PendingReview,
+    Published,
+    Unlisted,
+    Archived,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -72,16 +316,26 @@ pub struct CoursesObject {
     id: i128,
     creator_id: AccountId,
     title: String,
-    categories: CategoriesJson,
+    category_ids: Vec<i128>,
+    tags: Vec<String>,
     short_description: String,
     long_description: String,
-    img: String,
-    content: Vec<TemplateObject>,
+    img: MediaObject,
+    content: Vec<SectionObject>,
+    content_version: u32,
     price: Balance,
     price_certification: Balance,
-    inscriptions: Vec<AccountId>,
-    rating: f32,
-    reviews: Vec<Review>,
+    certification_validity_ns: Option<u64>,
+    enrollment_count: u64,
+    status: CourseStatus,
+    rejection_reason: Option<String>,
+    published_at: Option<u64>,
+    created_at: u64,
+    updated_at: u64,
+    rating_sum: u64,
+    rating_count: u64,
+    rating_histogram: [u64; 5],
+    review_count: u64,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
@@ -90,6 +344,52 @@ pub struct Review {
     user_id: AccountId,
     review: String,
     critics: i8,
+    created_at: u64,
+    edited_at: Option<u64>,
+    hidden: bool,
+    reply: Option<ReviewReply>,
+    helpful_votes: u32,
+    unhelpful_votes: u32,
+    payer_id: AccountId,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReviewReply {
+    reply: String,
+    replied_at: u64,
+    payer_id: AccountId,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ReviewVote {
+    helpful: bool,
+    payer_id: AccountId,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ReviewSort {
+    Newest,
+    Oldest,
+    MostHelpful,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReviewReport {
+    reporter_id: AccountId,
+    reason: String,
+    reported_at: u64,
+    payer_id: AccountId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReportedReview {
+    course_id: i128,
+    review: Review,
+    reports: Vec<ReviewReport>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -98,27 +398,99 @@ pub struct MarketView {
     id: i128,
     creator_id: AccountId,
     title: String,
-    categories: CategoriesJson,
+    categories: Vec<CategoriesJson>,
+    tags: Vec<String>,
     short_description: String,
     long_description: String,
-    img: String,
-    content: Vec<TemplateView>,
+    img: MediaObject,
+    content: Vec<SectionView>,
+    content_version: u32,
     price: Balance,
     price_certification: Balance,
-    rating: f32,
-    reviews: Vec<Review>,
+    certification_validity_ns: Option<u64>,
+    rating: u32,
+    rating_count: u64,
+    rating_histogram: [u64; 5],
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SortBy {
+    Newest,
+    Oldest,
+    PriceAsc,
+    PriceDesc,
+    Rating,
+    Enrollments,
+    Reviews,
+}
+
+// Every list view returns a page. `next_cursor` is opaque to clients: pass it back as `cursor`
+// to load the following page, it is None once the last page has been returned
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Page<T> {
+    items: Vec<T>,
+    total: u64,
+    next_cursor: Option<String>,
+}
+
+struct MarketFilter {
+    min_price: Option<Balance>,
+    max_price: Option<Balance>,
+    is_free: Option<bool>,
+    has_certification: Option<bool>,
+}
+
+impl MarketFilter {
+    fn is_empty(&self) -> bool {
+        self.min_price.is_none() && self.max_price.is_none() && self.is_free.is_none() && self.has_certification.is_none()
+    }
+
+    fn matches(&self, course: &CoursesObject) -> bool {
+        self.min_price.is_none_or(|x| course.price >= x)
+            && self.max_price.is_none_or(|x| course.price <= x)
+            && self.is_free.is_none_or(|x| (course.price == 0) == x)
+            // Certification is offered whenever the creator sells it
+            && self.has_certification.is_none_or(|x| (course.price_certification > 0) == x)
+    }
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
+    owner_id: AccountId,
     vault_id: AccountId,
     profiles: Vec<ProfileObject>,
     id_categories: i128,
     categories: Vec<CategoriesJson>,
     id_courses: i128,
     courses: UnorderedMap<i128, CoursesObject>,
+    content_versions: LookupMap<(i128, u32), ContentVersion>,
+    learner_keys: LookupMap<AccountId, String>,
+    key_services: LookupMap<i128, AccountId>,
+    key_envelopes: LookupMap<(i128, AccountId), KeyEnvelope>,
     administrators: Vec<AccountId>,
+    moderators: Vec<AccountId>,
+    platform_reserve: Balance,
+    max_categories: u8,
+    courses_by_category: LookupMap<i128, UnorderedSet<i128>>,
+    courses_by_tag: LookupMap<String, UnorderedSet<i128>>,
+    courses_by_creator: LookupMap<AccountId, UnorderedSet<i128>>,
+    courses_by_publish_time: TreeMap<(u64, i128), ()>,
+    courses_by_rating: TreeMap<(u32, i128), ()>,
+    courses_by_token: LookupMap<String, UnorderedSet<i128>>,
+    pending_courses: UnorderedSet<i128>,
+    review_reports: UnorderedMap<(i128, AccountId), Vec<ReviewReport>>,
+    course_inscriptions: LookupMap<i128, UnorderedSet<AccountId>>,
+    course_reviews: LookupMap<i128, UnorderedMap<AccountId, Review>>,
+    review_votes: LookupMap<i128, UnorderedMap<(AccountId, AccountId), ReviewVote>>,
+    storage_accounts: LookupMap<AccountId, StorageAccount>,
+    account_storage_usage: u64,
+    paused_scopes: Vec<PauseScope>,
+    upgrade_delay_ns: u64,
+    staged_upgrade_delay: Option<StagedDelay>,
+    staged_upgrade: Option<StagedUpgrade>,
 }
 
 #[near_bindgen]
@@ -132,19 +504,280 @@ impl Contract {
     }
 
     #[init]
-    pub fn new(_owner_id: ValidAccountId, vault_id: ValidAccountId) -> Self {
+    pub fn new(owner_id: ValidAccountId, vault_id: ValidAccountId) -> Self {
         assert!(!env::state_exists(), "Already initialized");
-        Self {
+        let mut this = Self {
+            owner_id: owner_id.to_string(),
             vault_id: vault_id.to_string(),
             profiles: Vec::new(),
             id_categories: 0,
             categories: Vec::new(),
             id_courses: 0,
             courses: UnorderedMap::new(b"s".to_vec()),
+            content_versions: LookupMap::new(b"v".to_vec()),
+            learner_keys: LookupMap::new(b"k".to_vec()),
+            key_services: LookupMap::new(b"ks".to_vec()),
+            key_envelopes: LookupMap::new(b"e".to_vec()),
             administrators: vec![
                                     "e-learning.testnet".to_string(),
                                     "juanochando.testnet".to_string(),
                                 ],
+            moderators: Vec::new(),
+            platform_reserve: 0,
+            max_categories: 3,
+            courses_by_category: LookupMap::new(b"cc".to_vec()),
+            courses_by_tag: LookupMap::new(b"tc".to_vec()),
+            courses_by_creator: LookupMap::new(b"uc".to_vec()),
+            courses_by_publish_time: TreeMap::new(b"p".to_vec()),
+            courses_by_rating: TreeMap::new(b"r".to_vec()),
+            courses_by_token: LookupMap::new(b"wc".to_vec()),
+            pending_courses: UnorderedSet::new(b"pr".to_vec()),
+            review_reports: UnorderedMap::new(b"rr".to_vec()),
+            course_inscriptions: LookupMap::new(b"ci".to_vec()),
+            course_reviews: LookupMap::new(b"cr".to_vec()),
+            review_votes: LookupMap::new(b"hv".to_vec()),
+            storage_accounts: LookupMap::new(b"sa".to_vec()),
+            account_storage_usage: 0,
+            paused_scopes: Vec::new(),
+            upgrade_delay_ns: DEFAULT_UPGRADE_DELAY_NS,
+            staged_upgrade_delay: None,
+            staged_upgrade: None,
+        };
+        this.measure_account_storage_usage();
+        this
+    }
+
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<ValidAccountId>, registration_only: Option<bool>) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.map(|x| x.to_string()).unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        match self.storage_accounts.get(&account_id) {
+            Some(mut account) => {
+                if registration_only {
+                    if amount > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(amount);
+                    }
+                } else {
+                    account.deposit += amount;
+                    self.storage_accounts.insert(&account_id, &account);
+                }
+            }
+            None => {
+                assert!(
+                    amount >= min_balance,
+                    "Artemis: attached deposit is less than the minimum storage balance : {}",
+                    min_balance
+                );
+                let deposit = if registration_only { min_balance } else { amount };
+                self.storage_accounts.insert(&account_id, &StorageAccount {
+                    deposit,
+                    used_bytes: self.account_storage_usage,
+                });
+                if amount > deposit {
+                    Promise::new(env::predecessor_account_id()).transfer(amount - deposit);
+                }
+            }
+        }
+
+        self.storage_balance(&account_id).expect("Artemis: Account is not registered for storage")
+    }
+
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self.storage_accounts.get(&account_id).expect("Artemis: Account is not registered for storage");
+
+        let available = account.deposit - storage_cost(account.used_bytes);
+        let amount = amount.map(|x| x.0).unwrap_or(available);
+        assert!(
+            amount <= available,
+            "Artemis: amount is greater than the available storage balance : {}",
+            available
+        );
+
+        account.deposit -= amount;
+        self.storage_accounts.insert(&account_id, &account);
+        if amount > 0 {
+            Promise::new(account_id.to_string()).transfer(amount);
+        }
+
+        self.storage_balance(&account_id).expect("Artemis: Account is not registered for storage")
+    }
+
+    // Data can't be burned on behalf of the account, so only accounts without stored data can leave
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let account = match self.storage_accounts.get(&account_id) {
+            Some(account) => account,
+            None => return false,
+        };
+
+        if force.unwrap_or(false) {
+            env::panic(b"Artemis: Forced unregistration is not supported");
+        }
+        if account.used_bytes > self.account_storage_usage {
+            env::panic(b"Artemis: Can't unregister an account that still stores data");
+        }
+
+        self.storage_accounts.remove(&account_id);
+        Promise::new(account_id).transfer(account.deposit + 1);
+        true
+    }
+
+    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_balance(account_id.as_ref())
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(storage_cost(self.account_storage_usage)),
+            max: None,
+        }
+    }
+
+    // Called by deploy_upgrade on the new code; state changes between versions are handled here
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let contract: Self = env::state_read().expect("Artemis: Contract is not initialized");
+        env::log(b"contract migrated");
+        contract
+    }
+
+    // The delay applies to upgrades staged after the change. Longer delays apply at once,
+    // shorter ones only once the current delay has passed, so the timelock can't be skipped
+    pub fn set_upgrade_delay(&mut self, delay_ns: U64) {
+        self.assert_owner();
+        let current = self.upgrade_delay();
+        self.upgrade_delay_ns = current;
+        self.staged_upgrade_delay = None;
+
+        if delay_ns.0 >= current {
+            self.upgrade_delay_ns = delay_ns.0;
+            return;
+        }
+
+        let staged = StagedDelay {
+            delay_ns: delay_ns.0,
+            available_at: env::block_timestamp() + current,
+        };
+        log_event("upgrade_delay_staged", json!({
+            "delay_ns": U64(staged.delay_ns),
+            "available_at": U64(staged.available_at),
+        }));
+        self.staged_upgrade_delay = Some(staged);
+    }
+
+    pub fn get_upgrade_delay(&self) -> U64 {
+        U64(self.upgrade_delay())
+    }
+
+    pub fn get_pending_upgrade_delay(&self) -> Option<UpgradeDelayView> {
+        self.staged_upgrade_delay.as_ref()
+            .filter(|x| env::block_timestamp() < x.available_at)
+            .map(|x| UpgradeDelayView {
+                delay_ns: U64(x.delay_ns),
+                available_at: U64(x.available_at),
+            })
+    }
+
+    // Staging announces the hex sha256 of the next code, which can be deployed once the delay has passed
+    pub fn stage_upgrade(&mut self, code_hash: String) -> UpgradeView {
+        self.assert_owner();
+        if !is_sha256_hex(&code_hash) {
+            env::panic(b"Artemis: Code hash must be a hex sha256");
+        }
+
+        let staged = StagedUpgrade {
+            code_hash: code_hash.to_lowercase(),
+            staged_at: env::block_timestamp(),
+            available_at: env::block_timestamp() + self.upgrade_delay(),
+        };
+        log_event("upgrade_staged", json!({
+            "code_hash": staged.code_hash,
+            "available_at": U64(staged.available_at),
+        }));
+        self.staged_upgrade = Some(staged);
+
+        self.get_pending_upgrade().expect("Artemis: No upgrade is staged")
+    }
+
+    pub fn cancel_upgrade(&mut self) {
+        self.assert_owner();
+        let staged = self.staged_upgrade.take().expect("Artemis: No upgrade is staged");
+        log_event("upgrade_cancelled", json!({
+            "code_hash": staged.code_hash,
+        }));
+    }
+
+    pub fn get_pending_upgrade(&self) -> Option<UpgradeView> {
+        self.staged_upgrade.as_ref().map(|x| UpgradeView {
+            code_hash: x.code_hash.to_string(),
+            staged_at: U64(x.staged_at),
+            available_at: U64(x.available_at),
+        })
+    }
+
+    // The code is passed borsh serialized, as raw bytes are too large for JSON arguments
+    pub fn deploy_upgrade(&mut self, #[serializer(borsh)] code: Vec<u8>) -> Promise {
+        self.assert_owner();
+        let staged = self.staged_upgrade.as_ref().expect("Artemis: No upgrade is staged");
+        if env::block_timestamp() < staged.available_at {
+            env::panic(format!("Artemis: Upgrade is timelocked until {}", staged.available_at).as_bytes());
+        }
+        if to_hex(&env::sha256(&code)) != staged.code_hash {
+            env::panic(b"Artemis: Code does not match the staged hash");
+        }
+
+        log_event("upgrade_deployed", json!({
+            "code_hash": staged.code_hash,
+        }));
+        self.staged_upgrade = None;
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), Vec::new(), 0, GAS_FOR_MIGRATE)
+    }
+
+    // Emergency stop: paused methods fail while every view keeps working
+    pub fn pause(&mut self, scope: PauseScope) -> PauseStatus {
+        self.assert_owner_or_admin();
+        if !self.paused_scopes.contains(&scope) {
+            self.paused_scopes.push(scope);
+            log_event("paused", json!({
+                "scope": scope,
+                "account_id": env::signer_account_id(),
+            }));
+        }
+        self.get_pause_status()
+    }
+
+    // Lifting All leaves scopes that were paused on their own in place
+    pub fn unpause(&mut self, scope: PauseScope) -> PauseStatus {
+        self.assert_owner_or_admin();
+        if self.paused_scopes.contains(&scope) {
+            self.paused_scopes.retain(|x| *x != scope);
+            log_event("unpaused", json!({
+                "scope": scope,
+                "account_id": env::signer_account_id(),
+            }));
+        }
+        self.get_pause_status()
+    }
+
+    pub fn get_pause_status(&self) -> PauseStatus {
+        PauseStatus {
+            all: self.paused_scopes.contains(&PauseScope::All),
+            purchases: self.paused_scopes.contains(&PauseScope::Purchases),
+            publishing: self.paused_scopes.contains(&PauseScope::Publishing),
+            reviews: self.paused_scopes.contains(&PauseScope::Reviews),
+            certification: self.paused_scopes.contains(&PauseScope::Certification),
         }
     }
 
@@ -159,135 +792,255 @@ impl Contract {
 
     pub fn delete_admin(&mut self, user_id: AccountId) {      
         self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can set categories");
-        let index = self.administrators.iter().position(|x| x == &user_id.to_string()).expect("the user is not in the list of administrators");
+        let index = self.administrators.iter().position(|x| x == &user_id).expect("the user is not in the list of administrators");
         self.administrators.remove(index);
     }
 
-    pub fn get_profile(&self, user_id: Option<AccountId>) -> Vec<ProfileObject> {
-        let mut profiles = self.profiles.clone();
-
-        if user_id.is_some() {
-            profiles = self.profiles.iter().filter(|x| x.user_id == user_id.clone().unwrap()).map(|x| ProfileObject {
-                user_id: x.user_id.to_string(),
-                purchased_courses: x.purchased_courses.clone(),
-            }).collect();
+    pub fn set_moderator(&mut self, user_id: AccountId) {
+        self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can set moderators");
+        let valid = self.moderators.iter().find(|&x| x == &user_id);
+        if valid.is_some() {
+            env::panic(b"the user is already in the list of moderators");
         }
-        profiles
+        self.moderators.push(user_id);
+    }
+
+    pub fn delete_moderator(&mut self, user_id: AccountId) {
+        self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can delete moderators");
+        let index = self.moderators.iter().position(|x| x == &user_id).expect("the user is not in the list of moderators");
+        self.moderators.remove(index);
+    }
+
+    pub fn get_moderators(&self, cursor: Option<String>, limit: Option<u64>) -> Page<AccountId> {
+        offset_page(self.moderators.iter().collect(), cursor, limit, |x| Some(x.to_string()))
+    }
+
+    pub fn get_profile(&self, user_id: Option<AccountId>, cursor: Option<String>, limit: Option<u64>) -> Page<ProfileObject> {
+        let profiles: Vec<&ProfileObject> = self.profiles.iter()
+            .filter(|x| user_id.as_ref().is_none_or(|user_id| x.user_id == *user_id))
+            .collect();
+
+        offset_page(profiles, cursor, limit, |x| Some((*x).clone()))
     }
 
-    pub fn set_category(&mut self, name: String, img: String) -> CategoriesJson {      
+    #[payable]
+    pub fn set_category(&mut self, name: String, img: String, parent_id: Option<i128>, order: u32, slug: String) -> CategoriesJson {      
         self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can set categories");
+        if let Some(parent) = parent_id {
+            self.assert_category_exists(parent);
+        }
+        let slug = self.valid_category_slug(&slug, None);
         self.id_categories += 1;
         let data = CategoriesJson {
             id: self.id_categories,
             name: name.to_string(),
             img: img.to_string(),
+            parent_id,
+            order,
+            slug,
         };
         
         self.categories.push(data.clone());
+        self.pay_storage(serialized_len(&data));
         env::log(b"category Created");
         
         data
     }
 
-    pub fn put_category(&mut self, category_id: i128, name: String, img: String) -> CategoriesJson {
+    pub fn put_category(&mut self, category_id: i128, name: String, img: String, parent_id: Option<i128>, order: u32, slug: String) -> CategoriesJson {
         self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only admins can edit categories");
         let index = self.categories.iter().position(|x| x.id == category_id).expect("Category does not exist");
+        if let Some(parent) = parent_id {
+            self.assert_category_exists(parent);
+            // The new parent can't be the category itself or one of its descendants
+            if self.category_descendants(category_id).contains(&parent) {
+                env::panic(b"Artemis: Category parent would create a cycle");
+            }
+        }
+        let slug = self.valid_category_slug(&slug, Some(category_id));
+        let previous_len = serialized_len(&self.categories[index]);
         self.categories[index].name = name.to_string();
         self.categories[index].img = img.to_string();
+        self.categories[index].parent_id = parent_id;
+        self.categories[index].order = order;
+        self.categories[index].slug = slug;
 
+        self.pay_storage(serialized_len(&self.categories[index]) - previous_len);
         env::log(b"Category Update");
 
-        CategoriesJson {
-            id: category_id,
-            name: name.to_string(),
-            img: img.to_string(),
-        }
+        self.categories[index].clone()
     }
 
-    pub fn get_category(&self, category_id: Option<i128>) -> Vec<CategoriesJson> {
-        let mut categories = self.categories.clone();
+    pub fn get_category(&self, category_id: Option<i128>, cursor: Option<String>, limit: Option<u64>) -> Page<CategoriesJson> {
+        let categories: Vec<&CategoriesJson> = self.categories.iter()
+            .filter(|x| category_id.is_none_or(|category_id| x.id == category_id))
+            .collect();
 
-        if category_id.is_some() {
-            categories = self.categories.iter().filter(|x| x.id == category_id.unwrap()).map(|x| CategoriesJson {
-                id: x.id,
-                name: x.name.to_string(),
-                img: x.img.to_string(),
-            }).collect();
-        }
-        categories
+        offset_page(categories, cursor, limit, |x| Some((*x).clone()))
     }
 
-    pub fn delete_category(&mut self, category_id: i128) {
+    pub fn get_category_tree(&self) -> Vec<CategoryNode> {
+        self.category_nodes(None)
+    }
+
+    // Courses still using the category must be moved to `reassign_to` first
+    pub fn delete_category(&mut self, category_id: i128, reassign_to: Option<i128>) {
         self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only admins can edit categories");
         let index = self.categories.iter().position(|x| x.id == category_id).expect("Category does not exist");
-        self.categories.remove(index);
+
+        let initial_storage_usage = env::storage_usage();
+        let in_use: Vec<i128> = self.courses_by_category.get(&category_id).map(|x| x.to_vec()).unwrap_or_default();
+
+        if !in_use.is_empty() {
+            let target = reassign_to.expect("Artemis: Category is still used by courses");
+            if target == category_id {
+                env::panic(b"Artemis: Can't reassign courses to the deleted category");
+            }
+            self.assert_category_exists(target);
+            for id in in_use {
+                let mut course = self.courses.get(&id).expect("Artemis: Course does not exist");
+                self.unindex_course(&course);
+                course.category_ids.retain(|x| *x != category_id && *x != target);
+                course.category_ids.push(target);
+                self.courses.insert(&id, &course);
+                self.index_course(&course);
+            }
+        }
+
+        // Subcategories move up to the deleted category's parent
+        let parent_id = self.categories[index].parent_id;
+        for category in self.categories.iter_mut().filter(|x| x.parent_id == Some(category_id)) {
+            category.parent_id = parent_id;
+        }
+
+        let category = self.categories.remove(index);
+        self.pay_storage(env::storage_usage() as i64 - initial_storage_usage as i64 - serialized_len(&category));
 
         env::log(b"Category deleted");
     }
     
+    pub fn set_max_categories(&mut self, max_categories: u8) {
+        self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only admins can edit categories");
+        assert!(max_categories > 0, "Artemis: Courses need at least one category");
+        self.max_categories = max_categories;
+    }
+
+    pub fn get_max_categories(&self) -> u8 {
+        self.max_categories
+    }
+
+    #[payable]
+    #[allow(clippy::too_many_arguments)]
     pub fn publish_course(&mut self, 
         title: String,
-        categories: CategoriesJson,
+        category_ids: Vec<i128>,
+        tags: Vec<String>,
         short_description: String,
         long_description: String,
-        img: String,
-        content: Vec<TemplateObject>,
+        img: MediaObject,
+        content: Vec<SectionObject>,
         price: U128,
         price_certification: U128,
+        certification_validity_ns: Option<U64>,
     ) -> CoursesObject {
+        self.assert_not_paused(PauseScope::Publishing);
+        let initial_storage_usage = env::storage_usage();
+        let category_ids = self.valid_category_ids(category_ids);
+        let tags = normalize_tags(tags);
+        assert_valid_media(&img);
+        content.iter().for_each(assert_valid_section);
         
         self.id_courses += 1;
         let data = CoursesObject {
             id: self.id_courses,
             creator_id: env::signer_account_id().to_string(),
             title: title.to_string(),
-            categories: categories,
+            category_ids,
+            tags,
             short_description: short_description.to_string(),
             long_description: long_description.to_string(),
-            img: img.to_string(),
-            content: content,
+            img,
+            content,
+            content_version: 1,
             price: price.0,
             price_certification: price_certification.0,
-            inscriptions: Vec::new(),
-            rating: 0.0,
-            reviews: Vec::new(),
+            certification_validity_ns: certification_validity_ns.map(|x| x.0),
+            enrollment_count: 0,
+            status: CourseStatus::Draft,
+            rejection_reason: None,
+            published_at: None,
+            created_at: env::block_timestamp(),
+            updated_at: env::block_timestamp(),
+            rating_sum: 0,
+            rating_count: 0,
+            rating_histogram: [0; 5],
+            review_count: 0,
         };
 
         self.courses.insert(&self.id_courses, &data);
+        self.index_course(&data);
+        self.content_versions.insert(&(data.id, 1), &ContentVersion {
+            version: 1,
+            created_at: env::block_timestamp(),
+            changelog: "Initial version".to_string(),
+            img: data.img.clone(),
+            content: data.content.clone(),
+        });
+        self.charge_storage(initial_storage_usage);
         env::log(b"published course");
         data
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn put_course(&mut self, 
         course_id: i128,
         title: String,
-        categories: CategoriesJson,
+        category_ids: Vec<i128>,
+        tags: Vec<String>,
         short_description: String,
         long_description: String,
-        img: String,
         price: U128,
         price_certification: U128,
+        certification_validity_ns: Option<U64>,
     ) -> CoursesObject {
+        self.assert_not_paused(PauseScope::Publishing);
         let course = self.courses.get(&course_id).expect("Course does not exist");
+        let category_ids = self.valid_category_ids(category_ids);
+        let tags = normalize_tags(tags);
 
-        if course.creator_id == env::signer_account_id().to_string() {
+        if course.creator_id == env::signer_account_id() {
+            let initial_storage_usage = env::storage_usage();
+            self.unindex_course(&course);
+            self.unindex_listing(&course);
             let data = CoursesObject {
                 id: course.id,
                 creator_id: course.creator_id,
-                title: title,
-                categories: categories,
+                title,
+                category_ids,
+                tags,
                 short_description: short_description.to_string(),
                 long_description: long_description.to_string(),
-                img: img.to_string(),
+                img: course.img,
                 content: course.content,
+                content_version: course.content_version,
                 price: price.0,
                 price_certification: price_certification.0,
-                inscriptions: course.inscriptions,
-                rating: course.rating,
-                reviews: course.reviews,
+                certification_validity_ns: certification_validity_ns.map(|x| x.0),
+                enrollment_count: course.enrollment_count,
+                status: course.status,
+                rejection_reason: course.rejection_reason,
+                published_at: course.published_at,
+                created_at: course.created_at,
+                updated_at: env::block_timestamp(),
+                rating_sum: course.rating_sum,
+                rating_count: course.rating_count,
+                rating_histogram: course.rating_histogram,
+                review_count: course.review_count,
             };
             self.courses.insert(&course_id, &data);
+            self.index_course(&data);
+            self.index_listing(&data);
+            self.charge_storage(initial_storage_usage);
             env::log(b"updated course");
             data
         } else {
@@ -295,189 +1048,636 @@ impl Contract {
         }
     }
 
-    pub fn get_courses_intructor(&self, user_id: Option<String>) -> Vec<CoursesObject> {
-        if user_id.is_some() {
-            self.courses.iter().filter(|(_k, x)| x.creator_id == user_id.clone().unwrap().to_string()).map(|(_k, x)| CoursesObject {
-                id: x.id,
-                creator_id: x.creator_id.to_string(),
-                title: x.title.to_string(),
-                categories: x.categories.clone(),
-                short_description: x.short_description.to_string(),
-                long_description: x.long_description.to_string(),
-                img: x.img.to_string(),
-                content: x.content.clone(),
-                price: x.price,
-                price_certification: x.price_certification,
-                inscriptions: x.inscriptions.clone(),
-                rating: x.rating,
-                reviews: x.reviews.clone(),
-            }).collect()
-        } else {
-            env::panic(b"Not user");
-        }
+    pub fn add_section(&mut self, course_id: i128, title: String) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        content.push(SectionObject {
+            title,
+            lessons: Vec::new(),
+        });
+        self.save_course_content(&mut course, content, "Section added");
+        env::log(b"section added");
+        course.content.iter().map(section_view).collect()
     }
 
-    pub fn get_courses_purchased(&self, user_id: String) -> Vec<CoursesObject> {
-        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+    pub fn put_section(&mut self, course_id: i128, section_idx: u32, title: String) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        let section = content.get_mut(section_idx as usize).expect("Artemis: Section does not exist");
+        section.title = title;
+        self.save_course_content(&mut course, content, "Section renamed");
+        env::log(b"section updated");
+        course.content.iter().map(section_view).collect()
+    }
 
-        let mut courses_purchased = Vec::new();
+    pub fn delete_section(&mut self, course_id: i128, section_idx: u32) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        assert!((section_idx as usize) < content.len(), "Artemis: Section does not exist");
+        content.remove(section_idx as usize);
+        self.save_course_content(&mut course, content, "Section deleted");
+        env::log(b"section deleted");
+        course.content.iter().map(section_view).collect()
+    }
 
-        for i in 0..self.profiles[index].purchased_courses.len() {
-            courses_purchased.push(self.courses.get(&self.profiles[index].purchased_courses[i].course_id).expect("Artemis: Course does not exists"));
-        }
+    pub fn add_lesson(&mut self, course_id: i128, section_idx: u32, lesson: TemplateObject) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        assert_valid_lesson(&lesson);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        let section = content.get_mut(section_idx as usize).expect("Artemis: Section does not exist");
+        section.lessons.push(lesson);
+        self.save_course_content(&mut course, content, "Lesson added");
+        env::log(b"lesson added");
+        course.content.iter().map(section_view).collect()
+    }
 
-        courses_purchased
+    pub fn put_lesson(&mut self, course_id: i128, section_idx: u32, lesson_idx: u32, lesson: TemplateObject) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        assert_valid_lesson(&lesson);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        let section = content.get_mut(section_idx as usize).expect("Artemis: Section does not exist");
+        let current = section.lessons.get_mut(lesson_idx as usize).expect("Artemis: Lesson does not exist");
+        let reference = &current.content;
+        if reference.content_hash != lesson.content.content_hash || reference.locator != lesson.content.locator || reference.media_type != lesson.content.media_type {
+            env::panic(b"Artemis: Lesson content can only change through update_course_content");
+        }
+        *current = lesson;
+        self.save_course_content(&mut course, content, "Lesson updated");
+        env::log(b"lesson updated");
+        course.content.iter().map(section_view).collect()
     }
 
-    pub fn get_course_id(&self, user_id: String, course_id: i128) -> CoursesObject {
-        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+    pub fn move_lesson(&mut self,
+        course_id: i128,
+        section_idx: u32,
+        lesson_idx: u32,
+        to_section_idx: u32,
+        to_lesson_idx: u32,
+    ) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        assert!((to_section_idx as usize) < content.len(), "Artemis: Section does not exist");
+        let section = content.get_mut(section_idx as usize).expect("Artemis: Section does not exist");
+        assert!((lesson_idx as usize) < section.lessons.len(), "Artemis: Lesson does not exist");
+        let lesson = section.lessons.remove(lesson_idx as usize);
 
-        self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Not permission");
+        let target = &mut content[to_section_idx as usize];
+        assert!((to_lesson_idx as usize) <= target.lessons.len(), "Artemis: Lesson position out of bounds");
+        target.lessons.insert(to_lesson_idx as usize, lesson);
 
-        let course = self.courses.get(&course_id).expect("Course does not exist");
-        
-        course
+        self.save_course_content(&mut course, content, "Lesson moved");
+        env::log(b"lesson moved");
+        course.content.iter().map(section_view).collect()
     }
 
-    pub fn get_pass_certification(&self, user_id: String, course_id: i128) -> CoursePurchased {
-        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+    pub fn delete_lesson(&mut self, course_id: i128, section_idx: u32, lesson_idx: u32) -> Vec<SectionView> {
+        self.assert_not_paused(PauseScope::Publishing);
+        let mut course = self.creator_course(course_id);
+        let mut content = course.content.clone();
+        let section = content.get_mut(section_idx as usize).expect("Artemis: Section does not exist");
+        assert!((lesson_idx as usize) < section.lessons.len(), "Artemis: Lesson does not exist");
+        section.lessons.remove(lesson_idx as usize);
+        self.save_course_content(&mut course, content, "Lesson deleted");
+        env::log(b"lesson deleted");
+        course.content.iter().map(section_view).collect()
+    }
 
-        let index_pass = self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Not permission");
+    pub fn update_course_content(&mut self,
+        course_id: i128,
+        content: Vec<SectionObject>,
+        img: Option<MediaObject>,
+        changelog: String,
+    ) -> ContentVersionView {
+        self.assert_not_paused(PauseScope::Publishing);
+        content.iter().for_each(assert_valid_section);
+        let initial_storage_usage = env::storage_usage();
+        let mut course = self.creator_course(course_id);
 
-        self.profiles[index].purchased_courses[index_pass].clone()
+        if let Some(img) = img {
+            assert_valid_media(&img);
+            if img.sha256 != course.img.sha256 {
+                log_event("course_img_hash_updated", json!({
+                    "course_id": course_id,
+                    "version": course.content_version + 1,
+                    "old_hash": course.img.sha256,
+                    "new_hash": img.sha256,
+                }));
+            }
+            course.img = img;
+        }
+
+        let version = self.add_content_version(&mut course, content, changelog);
+        self.charge_storage(initial_storage_usage);
+        env::log(b"course content updated");
+
+        ContentVersionView {
+            version: version.version,
+            created_at: U64(version.created_at),
+            changelog: version.changelog,
+        }
     }
 
-    pub fn get_market_courses(&self,
+    pub fn get_course_versions(&self, course_id: i128, cursor: Option<String>, limit: Option<u64>) -> Page<ContentVersionView> {
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        offset_page((1..=course.content_version).collect(), cursor, limit, |v| {
+            let version = self.content_versions.get(&(course_id, *v))?;
+            Some(ContentVersionView {
+                version: version.version,
+                created_at: U64(version.created_at),
+                changelog: version.changelog,
+            })
+        })
+    }
+
+    pub fn get_course_version(&self, user_id: String, course_id: i128, version: u32) -> ContentVersion {
+        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+
+        self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Not permission");
+
+        self.content_versions.get(&(course_id, version)).expect("Artemis: Content version does not exist")
+    }
+
+    // lesson_idx counts lessons across all sections, in curriculum order
+    pub fn verify_lesson_hash(&self, course_id: i128, lesson_idx: u32, hash: String) -> bool {
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        let lesson = lessons(&course.content).nth(lesson_idx as usize).expect("Artemis: Lesson does not exist");
+        lesson.content.content_hash.eq_ignore_ascii_case(&hash)
+    }
+
+    pub fn verify_img_hash(&self, course_id: i128, hash: String) -> bool {
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        course.img.sha256.eq_ignore_ascii_case(&hash)
+    }
+
+    pub fn register_learner_key(&mut self, public_key: String) {
+        if public_key.is_empty() {
+            env::panic(b"Artemis: Public key can't be empty");
+        }
+        let initial_storage_usage = env::storage_usage();
+        self.learner_keys.insert(&env::signer_account_id(), &public_key);
+        self.charge_storage(initial_storage_usage);
+        env::log(b"learner key registered");
+    }
+
+    pub fn get_learner_key(&self, user_id: AccountId) -> Option<String> {
+        self.learner_keys.get(&user_id)
+    }
+
+    pub fn set_course_key_service(&mut self, course_id: i128, key_service: Option<AccountId>) {
+        self.creator_course(course_id);
+        let initial_storage_usage = env::storage_usage();
+        match key_service {
+            Some(account_id) => self.key_services.insert(&course_id, &account_id),
+            None => self.key_services.remove(&course_id),
+        };
+        self.charge_storage(initial_storage_usage);
+        env::log(b"key service updated");
+    }
+
+    pub fn set_key_envelopes(&mut self, course_id: i128, envelopes: Vec<KeyEnvelopeInput>) {
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        let signer = env::signer_account_id();
+        if course.creator_id != signer && self.key_services.get(&course_id) != Some(signer) {
+            env::panic(b"No permission");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let mut replaced_bytes = 0;
+        for envelope in envelopes {
+            if !self.is_enrolled(course_id, &envelope.user_id) {
+                env::panic(b"Artemis: User is not enrolled in the course");
+            }
+            let learner_public_key = self.learner_keys.get(&envelope.user_id).expect("Artemis: Learner has no registered key");
+
+            // A replaced envelope is released to whoever paid for it before the new one is charged
+            let key = (course_id, envelope.user_id);
+            let before_removal = env::storage_usage();
+            if let Some(previous) = self.key_envelopes.remove(&key) {
+                let bytes = env::storage_usage() as i64 - before_removal as i64;
+                self.settle_storage(&previous.payer_id, bytes);
+                replaced_bytes += bytes;
+            }
+            self.key_envelopes.insert(&key, &KeyEnvelope {
+                learner_public_key,
+                encrypted_key: envelope.encrypted_key,
+                payer_id: env::predecessor_account_id(),
+            });
+        }
+        self.pay_storage(env::storage_usage() as i64 - initial_storage_usage as i64 - replaced_bytes);
+        env::log(b"key envelopes stored");
+    }
+
+    // Enrolled learners with a registered key who have no envelope for it yet
+    pub fn get_pending_key_envelopes(&self, course_id: i128, cursor: Option<String>, limit: Option<u64>) -> Page<(AccountId, String)> {
+        let pending: Vec<(AccountId, String)> = self.enrolled_users(course_id).iter().filter_map(|user_id| {
+            let public_key = self.learner_keys.get(user_id)?;
+            match self.key_envelopes.get(&(course_id, user_id.to_string())) {
+                Some(envelope) if envelope.learner_public_key == public_key => None,
+                _ => Some((user_id.to_string(), public_key)),
+            }
+        }).collect();
+
+        offset_page(pending, cursor, limit, |x| Some(x.clone()))
+    }
+
+    pub fn get_key_envelope(&self, user_id: AccountId, course_id: i128) -> KeyEnvelope {
+        if !self.is_enrolled(course_id, &user_id) {
+            env::panic(b"Not permission");
+        }
+        self.key_envelopes.get(&(course_id, user_id)).expect("Artemis: Key envelope does not exist")
+    }
+
+    pub fn get_preview_lesson(&self, course_id: i128, section_idx: u32, lesson_idx: u32) -> TemplateObject {
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        let section = course.content.get(section_idx as usize).expect("Artemis: Section does not exist");
+        let lesson = section.lessons.get(lesson_idx as usize).expect("Artemis: Lesson does not exist");
+        if !lesson.free_preview {
+            env::panic(b"Artemis: Lesson is not a free preview");
+        }
+        lesson.clone()
+    }
+
+    pub fn submit_course(&mut self, course_id: i128) -> CourseStatus {
+        self.assert_not_paused(PauseScope::Publishing);
+        let mut course = self.creator_course(course_id);
+        if course.status != CourseStatus::Draft {
+            env::panic(b"Artemis: Only drafts can be submitted for review");
+        }
+        let initial_storage_usage = env::storage_usage();
+        course.status = CourseStatus::PendingReview;
+        course.rejection_reason = None;
+        self.courses.insert(&course_id, &course);
+        self.pending_courses.insert(&course_id);
+        self.settle_storage(&course.creator_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+        env::log(b"course submitted for review");
+        course.status
+    }
+
+    pub fn approve_course(&mut self, course_id: i128) -> CourseStatus {
+        self.assert_moderator();
+        let mut course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        if course.status != CourseStatus::PendingReview {
+            env::panic(b"Artemis: Course is not pending review");
+        }
+        if course.creator_id == env::signer_account_id() {
+            env::panic(b"Artemis: Creators cannot approve their own course");
+        }
+        let initial_storage_usage = env::storage_usage();
+        course.status = CourseStatus::Published;
+        course.published_at = Some(course.published_at.unwrap_or_else(env::block_timestamp));
+        self.courses.insert(&course_id, &course);
+        self.pending_courses.remove(&course_id);
+        self.index_listing(&course);
+        // The listing is part of the course, so the creator pays for it rather than the moderator
+        self.settle_storage(&course.creator_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+        env::log(b"course approved");
+        course.status
+    }
+
+    pub fn reject_course(&mut self, course_id: i128, reason: String) -> CourseStatus {
+        self.assert_moderator();
+        let mut course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        if course.status != CourseStatus::PendingReview {
+            env::panic(b"Artemis: Course is not pending review");
+        }
+        let initial_storage_usage = env::storage_usage();
+        course.status = CourseStatus::Draft;
+        course.rejection_reason = Some(reason);
+        self.courses.insert(&course_id, &course);
+        self.pending_courses.remove(&course_id);
+        self.settle_storage(&course.creator_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+        env::log(b"course rejected");
+        course.status
+    }
+
+    // Creators move a live course between Published, Unlisted and Archived
+    pub fn set_course_status(&mut self, course_id: i128, status: CourseStatus) -> CourseStatus {
+        let mut course = self.creator_course(course_id);
+        let allowed = matches!(
+            (&course.status, &status),
+            (CourseStatus::Published, CourseStatus::Unlisted)
+                | (CourseStatus::Unlisted, CourseStatus::Published)
+                | (CourseStatus::Published, CourseStatus::Archived)
+                | (CourseStatus::Unlisted, CourseStatus::Archived)
+        );
+        if !allowed {
+            env::panic(b"Artemis: Invalid course status change");
+        }
+        let initial_storage_usage = env::storage_usage();
+        self.unindex_listing(&course);
+        course.status = status;
+        self.courses.insert(&course_id, &course);
+        self.index_listing(&course);
+        self.settle_storage(&course.creator_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+        env::log(b"course status updated");
+        course.status
+    }
+
+    pub fn get_pending_courses(&self, cursor: Option<String>, limit: Option<u64>) -> Page<CoursesObject> {
+        let mut ids = self.pending_courses.to_vec();
+        ids.sort_unstable();
+        offset_page(ids, cursor, limit, |id| self.courses.get(id))
+    }
+
+    // Drafts and courses pending review stay private, see get_draft_courses
+    pub fn get_courses_intructor(&self, user_id: Option<String>, cursor: Option<String>, limit: Option<u64>) -> Page<CoursesObject> {
+        if let Some(user_id) = user_id {
+            let courses: Vec<CoursesObject> = self.creator_courses(&user_id).into_iter()
+                .filter(|x| !matches!(x.status, CourseStatus::Draft | CourseStatus::PendingReview))
+                .collect();
+            offset_page(courses, cursor, limit, |x| Some(x.clone()))
+        } else {
+            env::panic(b"Not user");
+        }
+    }
+
+    // The caller's own drafts and courses pending review. Views carry no caller, so creators
+    // read them with a function call
+    pub fn get_draft_courses(&self, cursor: Option<String>, limit: Option<u64>) -> Page<CoursesObject> {
+        let courses: Vec<CoursesObject> = self.creator_courses(&env::predecessor_account_id()).into_iter()
+            .filter(|x| matches!(x.status, CourseStatus::Draft | CourseStatus::PendingReview))
+            .collect();
+        offset_page(courses, cursor, limit, |x| Some(x.clone()))
+    }
+
+    pub fn get_courses_purchased(&self, user_id: String, cursor: Option<String>, limit: Option<u64>) -> Page<CoursesObject> {
+        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+
+        offset_page(self.profiles[index].purchased_courses.clone(), cursor, limit, |x| {
+            Some(self.courses.get(&x.course_id).expect("Artemis: Course does not exists"))
+        })
+    }
+
+    // Enrollments, certifications and reviews of a learner, newest first
+    pub fn get_learner_activity(&self, user_id: AccountId, cursor: Option<String>, limit: Option<u64>) -> Page<ActivityEntry> {
+        let mut activity: Vec<ActivityEntry> = Vec::new();
+
+        if let Some(profile) = self.profiles.iter().find(|x| x.user_id == user_id) {
+            for purchased in profile.purchased_courses.iter() {
+                activity.push(ActivityEntry { kind: ActivityKind::Enrolled, course_id: purchased.course_id, timestamp: U64(purchased.purchased_at) });
+                if let Some(certified_at) = purchased.certified_at {
+                    activity.push(ActivityEntry { kind: ActivityKind::Certified, course_id: purchased.course_id, timestamp: U64(certified_at) });
+                }
+                if let Some(review) = self.course_review(purchased.course_id, &user_id) {
+                    activity.push(ActivityEntry { kind: ActivityKind::Reviewed, course_id: purchased.course_id, timestamp: U64(review.created_at) });
+                    if let Some(edited_at) = review.edited_at {
+                        activity.push(ActivityEntry { kind: ActivityKind::ReviewEdited, course_id: purchased.course_id, timestamp: U64(edited_at) });
+                    }
+                }
+            }
+        }
+
+        activity.sort_by_key(|x| std::cmp::Reverse(x.timestamp.0));
+        offset_page(activity, cursor, limit, |x| Some(ActivityEntry { kind: x.kind.clone(), course_id: x.course_id, timestamp: x.timestamp }))
+    }
+
+    // Enrollments of a course in the order they happened
+    pub fn get_enrollment_history(&self, course_id: i128, cursor: Option<String>, limit: Option<u64>) -> Page<EnrollmentEntry> {
+        let mut enrollments: Vec<EnrollmentEntry> = self.enrolled_users(course_id).iter().filter_map(|user_id| {
+            let profile = self.profiles.iter().find(|x| x.user_id == *user_id)?;
+            let purchased = profile.purchased_courses.iter().find(|x| x.course_id == course_id)?;
+            Some(EnrollmentEntry {
+                user_id: user_id.to_string(),
+                purchased_at: U64(purchased.purchased_at),
+                certified_at: purchased.certified_at.map(U64),
+            })
+        }).collect();
+
+        enrollments.sort_by_key(|x| x.purchased_at.0);
+        offset_page(enrollments, cursor, limit, |x| Some(EnrollmentEntry {
+            user_id: x.user_id.to_string(),
+            purchased_at: x.purchased_at,
+            certified_at: x.certified_at,
+        }))
+    }
+
+    pub fn get_course_id(&self, user_id: String, course_id: i128) -> CoursesObject {
+        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+
+        self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Not permission");
+
+        self.courses.get(&course_id).expect("Course does not exist")
+    }
+
+    pub fn get_pass_certification(&self, user_id: String, course_id: i128) -> CoursePurchased {
+        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
+
+        let index_pass = self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Not permission");
+
+        self.profiles[index].purchased_courses[index_pass].clone()
+    }
+
+    // Pages are cursor based: `next_cursor` is the sort key of the last item returned,
+    // so courses published between page loads don't shift the following pages
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_market_courses(&self,
         course_id: Option<i128>,
         creator_id: Option<AccountId>,
         category_id: Option<i128>,
-        from_index: Option<u128>,
+        tag: Option<String>,
+        sort_by: Option<SortBy>,
+        min_price: Option<U128>,
+        max_price: Option<U128>,
+        is_free: Option<bool>,
+        has_certification: Option<bool>,
+        cursor: Option<String>,
         limit: Option<u64>
-    ) -> Vec<MarketView> {
-
-        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
-        assert!((self.courses.len() as u128) > start_index, "Out of bounds, please use a smaller from_index.");
+    ) -> Page<MarketView> {
         let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
         assert_ne!(limit, 0, "Cannot provide limit of 0.");
+        let sort_by = sort_by.unwrap_or(SortBy::Oldest);
+        let cursor = cursor.map(|x| parse_cursor(&x));
+        let filter = MarketFilter {
+            min_price: min_price.map(|x| x.0),
+            max_price: max_price.map(|x| x.0),
+            is_free,
+            has_certification,
+        };
 
-        let mut result: Vec<CoursesObject> = self.courses.iter().map(|(_k, v)| v).collect::<Vec<CoursesObject>>();
+        // Unlisted courses can still be opened directly by id
+        if let Some(course) = course_id {
+            let items = self.courses.get(&course).into_iter()
+                .filter(|x| x.status == CourseStatus::Published || x.status == CourseStatus::Unlisted)
+                .filter(|x| creator_id.as_ref().is_none_or(|creator| x.creator_id == *creator))
+                .filter(|x| category_id.is_none_or(|category| self.category_descendants(category).iter().any(|id| x.category_ids.contains(id))))
+                .filter(|x| tag.as_ref().is_none_or(|tag| x.tags.contains(&normalize_tag(tag))))
+                .filter(|x| filter.matches(x))
+                .map(|x| self.market_view(&x)).collect::<Vec<MarketView>>();
+            let total = items.len() as u64;
+            return Page { items: if cursor.is_none() { items } else { Vec::new() }, total, next_cursor: None };
+        }
 
-        if creator_id.is_some() {
-            let creator = creator_id.unwrap().clone();
-            result = result.iter().filter(|x| x.creator_id == creator).map(|x| x.clone()).collect();
-        };
+        let ids = self.listed_course_ids(creator_id, category_id, tag);
 
-        if category_id.is_some() {
-            let category = category_id.unwrap().clone();
-            result = result.iter().filter(|x| x.categories.id == category).map(|x| x.clone()).collect();
+        let (page, total): (Vec<SortedCourse>, u64) = match (ids, sort_by) {
+            // Publish time sorts walk the index from the cursor without loading skipped courses
+            (None, SortBy::Oldest) | (None, SortBy::Newest) => {
+                let from = cursor.map(|(published_at, id)| (published_at as u64, id));
+                let keys: Box<dyn Iterator<Item = ((u64, i128), ())>> = match (sort_by, from) {
+                    (SortBy::Oldest, Some(key)) => Box::new(self.courses_by_publish_time.iter_from(key)),
+                    (SortBy::Oldest, None) => Box::new(self.courses_by_publish_time.iter()),
+                    (_, Some(key)) => Box::new(self.courses_by_publish_time.iter_rev_from(key)),
+                    (_, None) => Box::new(self.courses_by_publish_time.iter_rev()),
+                };
+                let page = keys.filter_map(|((published_at, id), _)| self.courses.get(&id).map(|x| ((published_at as u128, id), x)))
+                    .filter(|(_, x)| filter.matches(x))
+                    .take(limit)
+                    .collect();
+                let total = if filter.is_empty() {
+                    self.courses_by_publish_time.len()
+                } else {
+                    self.courses_by_publish_time.iter()
+                        .filter_map(|((_, id), _)| self.courses.get(&id))
+                        .filter(|x| filter.matches(x))
+                        .count() as u64
+                };
+                (page, total)
+            }
+            (ids, _) => {
+                let candidates: Vec<CoursesObject> = match ids {
+                    Some(ids) => ids.iter().filter_map(|id| self.courses.get(id)).collect(),
+                    None => self.courses_by_publish_time.iter().filter_map(|((_, id), _)| self.courses.get(&id)).collect(),
+                };
+                let descending = matches!(sort_by, SortBy::Newest | SortBy::PriceDesc | SortBy::Rating | SortBy::Enrollments | SortBy::Reviews);
+                let mut keyed: Vec<SortedCourse> = candidates.into_iter()
+                    .filter(|x| filter.matches(x))
+                    .map(|x| (market_sort_key(&x, sort_by), x))
+                    .collect();
+                let total = keyed.len() as u64;
+                keyed.retain(|(key, _)| cursor.is_none_or(|c| if descending { *key < c } else { *key > c }));
+                keyed.sort_by(|a, b| if descending { b.0.cmp(&a.0) } else { a.0.cmp(&b.0) });
+                keyed.truncate(limit);
+                (keyed, total)
+            }
         };
 
-        if course_id.is_some() {
-            let course = course_id.unwrap().clone();
-            result = result.iter().filter(|x| x.id == course).map(|x| x.clone()).collect();
+        let next_cursor = if page.len() == limit {
+            page.last().map(|((a, b), _)| format!("{}:{}", a, b))
+        } else {
+            None
         };
 
-        result.iter()
-        .skip(start_index as usize)
-        .take(limit)
-        .map(|x| MarketView {
-            id: x.id,
-            creator_id: x.creator_id.to_string(),
-            title: x.title.to_string(),
-            categories: x.categories.clone(),
-            short_description: x.short_description.to_string(),
-            long_description: x.long_description.to_string(),
-            content: x.content.iter().map(|x| TemplateView {
-                title: x.title.to_string(),
-                tipo: x.tipo,
-            }).collect(),
-            img: x.img.to_string(),
-            price: x.price,
-            price_certification: x.price_certification,
-            rating: x.rating,
-            reviews: x.reviews.clone(),
-        }).collect()
+        Page {
+            items: page.iter().map(|(_, x)| self.market_view(x)).collect(),
+            total,
+            next_cursor,
+        }
     }
 
-    pub fn get_recent_courses(&self,
-        number_courses: u64,
-    ) -> Vec<MarketView> {
-
-        if self.courses.len() > number_courses {
-            let index: u64 = self.courses.len() - number_courses;
-            let result: Vec<CoursesObject> = self.courses.iter().map(|(_k, v)| v).collect::<Vec<CoursesObject>>();
-
-            result.iter()
-            .skip(index as usize)
-            .map(|x| MarketView {
-                id: x.id,
-                creator_id: x.creator_id.to_string(),
-                title: x.title.to_string(),
-                categories: x.categories.clone(),
-                short_description: x.short_description.to_string(),
-                long_description: x.long_description.to_string(),
-                img: x.img.to_string(),
-                content: x.content.iter().map(|x| TemplateView {
-                    title: x.title.to_string(),
-                    tipo: x.tipo,
-                }).collect(),
-                price: x.price,
-                price_certification: x.price_certification,
-                rating: x.rating,
-                reviews: x.reviews.clone(),
-            }).collect()
-        } else {
-            self.courses.iter().map(|(_k, x)| MarketView {
-                id: x.id,
-                creator_id: x.creator_id.to_string(),
-                title: x.title.to_string(),
-                categories: x.categories.clone(),
-                short_description: x.short_description.to_string(),
-                long_description: x.long_description.to_string(),
-                img: x.img.to_string(),
-                content: x.content.iter().map(|x| TemplateView {
-                    title: x.title.to_string(),
-                    tipo: x.tipo,
-                }).collect(),
-                price: x.price,
-                price_certification: x.price_certification,
-                rating: x.rating,
-                reviews: x.reviews.clone(),
-            }).collect()
-        }  
+    // Published courses ranked by how many query terms appear in their title or short description
+    pub fn search_courses(&self, query: String, cursor: Option<String>, limit: Option<u64>) -> Page<MarketView> {
+        let mut terms = tokenize(&query);
+        terms.truncate(MAX_SEARCH_TERMS);
+
+        let mut counts: HashMap<i128, usize> = HashMap::new();
+        for term in terms.iter() {
+            if let Some(ids) = self.courses_by_token.get(term) {
+                for id in ids.iter() {
+                    *counts.entry(id).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut matches: Vec<(usize, i128)> = counts.into_iter().map(|(id, count)| (count, id)).collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        // Only published courses are indexed, so every match is listed and only the page is loaded
+        offset_page(matches, cursor, limit, |(_, id)| self.courses.get(id).map(|x| self.market_view(&x)))
+    }
+
+    // Newest first, by the publish timestamp stored on each course
+    pub fn get_recent_courses(&self, cursor: Option<String>, limit: Option<u64>) -> Page<MarketView> {
+        self.get_market_courses(None, None, None, None, Some(SortBy::Newest), None, None, None, None, cursor, limit)
     }
 
     pub fn delete_course(&mut self, course_id: i128) {
         let course = self.courses.get(&course_id).expect("Course does not exist");
 
-        if course.creator_id == env::signer_account_id().to_string() {
-            if course.inscriptions.len() == 0 {
-                self.courses.remove(&course_id);
+        if course.creator_id == env::signer_account_id() {
+            if course.enrollment_count == 0 {
+                self.remove_course_data(&course);
                 env::log(b"Course deleted")
             } else {
-                env::panic(b"Can't delete course")
+                env::panic(b"Can't delete course, use retire_course")
             }
         } else {
             env::panic(b"No permission")
         }
     }
 
-    pub fn get_course_size(&self,
-        creator_id: Option<AccountId>,
-        category_id: Option<i128>,) -> u64 {
-        let mut result: Vec<CoursesObject> = self.courses.iter().map(|(_k, v)| v).collect::<Vec<CoursesObject>>();
+    // Stops new sales and hides the course, enrolled learners keep content and certificates
+    pub fn retire_course(&mut self, course_id: i128) -> CourseStatus {
+        let mut course = self.creator_course(course_id);
+        if course.status == CourseStatus::Archived {
+            env::panic(b"Artemis: Course is already retired");
+        }
+        let initial_storage_usage = env::storage_usage();
+        self.unindex_listing(&course);
+        course.status = CourseStatus::Archived;
+        self.courses.insert(&course_id, &course);
+        self.settle_storage(&course.creator_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+        env::log(b"Course retired");
+        course.status
+    }
 
-        if creator_id.is_some() {
-            let creator = creator_id.unwrap().clone();
-            result = result.iter().filter(|x| x.creator_id == creator).map(|x| x.clone()).collect();
-        };
+    #[payable]
+    pub fn fund_reserve(&mut self) -> U128 {
+        self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can fund the reserve");
+        self.platform_reserve += env::attached_deposit();
+        U128(self.platform_reserve)
+    }
 
-        if category_id.is_some() {
-            let category = category_id.unwrap().clone();
-            result = result.iter().filter(|x| x.categories.id == category).map(|x| x.clone()).collect();
-        };
+    pub fn get_platform_reserve(&self) -> U128 {
+        U128(self.platform_reserve)
+    }
+
+    // Refunds every enrolled learner what they paid, out of the platform reserve
+    pub fn force_delete_course(&mut self, course_id: i128) {
+        self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can force delete courses");
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
 
-        result.len().try_into().unwrap()
+        let refunds: Vec<(AccountId, Balance)> = self.profiles.iter().filter_map(|profile| {
+            let purchased = profile.purchased_courses.iter().find(|k| k.course_id == course_id)?;
+            Some((profile.user_id.to_string(), purchased.purchase_price))
+        }).collect();
+
+        let total: Balance = refunds.iter().map(|(_, amount)| amount).sum();
+        assert!(
+            total <= self.platform_reserve,
+            "Artemis: platform reserve can't cover refunds : {}",
+            total
+        );
+        self.platform_reserve -= total;
+
+        for (user_id, amount) in refunds.iter() {
+            if *amount > 0 {
+                Promise::new(user_id.to_string()).transfer(*amount);
+            }
+        }
+
+        self.remove_course_data(&course);
+        log_event("course_force_deleted", json!({
+            "course_id": course_id,
+            "refunds": refunds.iter().map(|(user_id, amount)| json!({
+                "user_id": user_id,
+                "amount": U128(*amount),
+            })).collect::<Vec<Value>>(),
+        }));
+    }
+
+    pub fn get_course_size(&self,
+        creator_id: Option<AccountId>,
+        category_id: Option<i128>,
+        tag: Option<String>,) -> u64 {
+        match self.listed_course_ids(creator_id, category_id, tag) {
+            Some(ids) => ids.len() as u64,
+            None => self.courses_by_publish_time.len(),
+        }
     }
 
     #[payable]
@@ -485,13 +1685,16 @@ impl Contract {
         &mut self, 
         course_id: i128, 
     ) -> CoursesObject {
+        self.assert_not_paused(PauseScope::Purchases);
         let initial_storage_usage = env::storage_usage();
 
         let mut course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
 
-        let index = course.inscriptions.iter().position(|x| x.to_string() == env::signer_account_id().to_string());
+        if course.status != CourseStatus::Published && course.status != CourseStatus::Unlisted {
+            env::panic(b"Artemis: Course is not for sale");
+        }
 
-        if index.is_some() {
+        if self.is_enrolled(course_id, &env::signer_account_id()) {
             env::panic(b"Artemis: User already enrolled in the course");
         }
 
@@ -503,7 +1706,7 @@ impl Contract {
             price
         );
 
-        let for_vault = price as u128 * VAULT_FEE / 10_000u128;
+        let for_vault = price * VAULT_FEE / 10_000u128;
         let price_deducted = price - for_vault;
         Promise::new(course.creator_id.to_string()).transfer(price_deducted);
 
@@ -511,12 +1714,18 @@ impl Contract {
             Promise::new(self.vault_id.clone()).transfer(for_vault);
         }
 
-        refund_deposit(env::storage_usage() - initial_storage_usage, price);
-
-        course.inscriptions.push(env::signer_account_id().to_string());
+        let mut inscriptions = self.course_inscriptions.get(&course_id).unwrap_or_else(|| {
+            UnorderedSet::new([b"is".to_vec(), course_id.to_le_bytes().to_vec()].concat())
+        });
+        inscriptions.insert(&env::signer_account_id());
+        self.course_inscriptions.insert(&course_id, &inscriptions);
+        course.enrollment_count += 1;
         self.courses.insert(&course_id, &course);
 
-        self.profile_inscription(course_id);
+        let profile_bytes = self.profile_inscription(course_id, price);
+
+        // Profiles live in the contract state, which is only written after the call returns
+        refund_deposit(env::storage_usage() as i64 - initial_storage_usage as i64 + profile_bytes, price);
 
         course
     }
@@ -526,11 +1735,17 @@ impl Contract {
         &mut self, 
         course_id: i128, 
     ) -> CoursePurchased {
+        self.assert_not_paused(PauseScope::Certification);
         let initial_storage_usage = env::storage_usage();
 
         let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
 
         let index = self.profiles.iter().position(|x| x.user_id == env::signer_account_id()).expect("Profile does not exist");
+        let index_course = self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Course does not buy");
+
+        if self.profiles[index].purchased_courses[index_course].certification_revoked {
+            env::panic(b"Artemis: Certification has been revoked");
+        }
 
         let price_certification: Balance = course.price_certification;
         let attached_deposit = env::attached_deposit();
@@ -540,7 +1755,7 @@ impl Contract {
             price_certification
         );
 
-        let for_vault = price_certification as u128 * VAULT_FEE / 10_000u128;
+        let for_vault = price_certification * VAULT_FEE / 10_000u128;
         let price_deducted = price_certification - for_vault;
         Promise::new(course.creator_id.to_string()).transfer(price_deducted);
 
@@ -548,10 +1763,14 @@ impl Contract {
             Promise::new(self.vault_id.clone()).transfer(for_vault);
         }
 
-        refund_deposit(env::storage_usage() - initial_storage_usage, price_certification);
-
-        let index_course = self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Course does not buy");
+        let previous_len = serialized_len(&self.profiles[index].purchased_courses[index_course]);
         self.profiles[index].purchased_courses[index_course].pass_certification = true;
+        self.profiles[index].purchased_courses[index_course].certification_expires_at = course.certification_validity_ns.map(|validity| env::block_timestamp() + validity);
+        self.profiles[index].purchased_courses[index_course].certified_version = Some(course.content_version);
+        self.profiles[index].purchased_courses[index_course].certified_at = Some(env::block_timestamp());
+
+        let profile_bytes = serialized_len(&self.profiles[index].purchased_courses[index_course]) - previous_len;
+        refund_deposit(env::storage_usage() as i64 - initial_storage_usage as i64 + profile_bytes, price_certification);
 
         self.profiles[index].purchased_courses[index_course].clone()
     }
@@ -559,52 +1778,143 @@ impl Contract {
     pub fn change_pass_certification(&mut self, user_id: AccountId, course_id: i128,) -> CoursePurchased {      
         self.administrators.iter().find(|&x| x == &env::signer_account_id()).expect("Only administrators can set categories");
         
-        let index = self.profiles.iter().position(|x| x.user_id == user_id.to_string()).expect("Profile does not exist");
+        let index = self.profiles.iter().position(|x| x.user_id == user_id).expect("Profile does not exist");
 
         let index_course = self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Course does not buy");
 
         self.profiles[index].purchased_courses[index_course].pass_certification = false;
+        self.profiles[index].purchased_courses[index_course].certification_revoked = true;
+
+        self.profiles[index].purchased_courses[index_course].clone()
+    }
+
+    #[payable]
+    pub fn renew_certification(
+        &mut self, 
+        course_id: i128, 
+    ) -> CoursePurchased {
+        self.assert_not_paused(PauseScope::Certification);
+        let initial_storage_usage = env::storage_usage();
+
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        let validity = course.certification_validity_ns.expect("Artemis: Course certification does not expire");
+
+        let index = self.profiles.iter().position(|x| x.user_id == env::signer_account_id()).expect("Profile does not exist");
+        let index_course = self.profiles[index].purchased_courses.iter().position(|k| k.course_id == course_id).expect("Course does not buy");
+
+        let purchased = self.profiles[index].purchased_courses[index_course].clone();
+        if purchased.certification_revoked {
+            env::panic(b"Artemis: Certification has been revoked");
+        }
+        if !purchased.pass_certification {
+            env::panic(b"Artemis: User is not certified in the course");
+        }
+
+        let price_renewal: Balance = course.price_certification * (10_000u128 - RENEWAL_DISCOUNT) / 10_000u128;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= price_renewal,
+            "Artemis: attached deposit is less than price : {}",
+            price_renewal
+        );
+
+        let for_vault = price_renewal * VAULT_FEE / 10_000u128;
+        let price_deducted = price_renewal - for_vault;
+        Promise::new(course.creator_id.to_string()).transfer(price_deducted);
+
+        if for_vault != 0 {
+            Promise::new(self.vault_id.clone()).transfer(for_vault);
+        }
+
+        // Renewing before expiry extends from the current expiry, so learners don't lose paid time
+        let start = purchased.certification_expires_at.unwrap_or(0).max(env::block_timestamp());
+        self.profiles[index].purchased_courses[index_course].certification_expires_at = Some(start + validity);
+
+        let profile_bytes = serialized_len(&self.profiles[index].purchased_courses[index_course]) - serialized_len(&purchased);
+        refund_deposit(env::storage_usage() as i64 - initial_storage_usage as i64 + profile_bytes, price_renewal);
+
+        env::log(b"Certification renewed");
 
         self.profiles[index].purchased_courses[index_course].clone()
     }
 
+    pub fn get_certification_status(&self, user_id: AccountId, course_id: i128) -> CertificationView {
+        let purchased = self.profiles.iter()
+            .find(|x| x.user_id == user_id)
+            .and_then(|x| x.purchased_courses.iter().find(|k| k.course_id == course_id));
+
+        let (status, expires_at, certified_version) = match purchased {
+            Some(p) => (certification_status(p), p.certification_expires_at, p.certified_version),
+            None => (CertificationStatus::NotCertified, None, None),
+        };
+
+        CertificationView {
+            user_id,
+            course_id,
+            status,
+            expires_at: expires_at.map(U64),
+            certified_version,
+        }
+    }
+
+    #[payable]
     pub fn set_review(
         &mut self, 
         course_id: i128, 
         review: String,
         critics: i8,
     ) -> Review {
-
+        self.assert_not_paused(PauseScope::Reviews);
+        let initial_storage_usage = env::storage_usage();
         let mut course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        let signer = env::signer_account_id();
+
+        if course.creator_id == signer {
+            env::panic(b"Artemis: Creators cannot review their own course");
+        }
+        if !self.is_enrolled(course_id, &signer) {
+            env::panic(b"Artemis: Only enrolled learners can review the course");
+        }
+        if !(MIN_REVIEW_SCORE..=MAX_REVIEW_SCORE).contains(&critics) {
+            env::panic(b"Artemis: Review score must be between 1 and 5");
+        }
+        if review.chars().count() > MAX_REVIEW_LENGTH {
+            env::panic(b"Artemis: Review is too long");
+        }
 
-        let index = course.reviews.iter().position(|x| x.user_id == env::signer_account_id().to_string());
+        let previous = self.course_review(course_id, &signer);
+        if previous.as_ref().is_some_and(|x| x.hidden) {
+            env::panic(b"Artemis: Review has been hidden by a moderator");
+        }
+        if previous.as_ref().is_some_and(|x| x.payer_id != env::predecessor_account_id()) {
+            env::panic(b"Artemis: Review must be edited from the account that paid for it");
+        }
 
         let data = Review {
-            user_id: env::signer_account_id().to_string(),
+            user_id: signer.to_string(),
             review: review.to_string(),
-            critics: critics,
+            critics,
+            created_at: previous.as_ref().map(|x| x.created_at).unwrap_or_else(env::block_timestamp),
+            edited_at: previous.as_ref().map(|_| env::block_timestamp()),
+            hidden: false,
+            reply: previous.as_ref().and_then(|x| x.reply.clone()),
+            helpful_votes: previous.as_ref().map(|x| x.helpful_votes).unwrap_or_default(),
+            unhelpful_votes: previous.as_ref().map(|x| x.unhelpful_votes).unwrap_or_default(),
+            payer_id: env::predecessor_account_id(),
         };
 
-        if index.is_some() {
-            let ind = course.reviews.iter().position(|x| x.user_id == env::signer_account_id().to_string()).expect("Artemis: Review does not exist");
-            course.reviews[ind] = data.clone();
-            let mut cont = 0.0;
-            for item in &course.reviews {
-                cont += item.critics as f32;
-            }
-            course.rating = cont / (course.reviews.len() as f32);
-            self.courses.insert(&course_id, &course);
-            return data
-        }
+        self.unindex_listing(&course);
 
-        course.reviews.push(data.clone());
-        let mut cont = 0.0;
-        for item in &course.reviews {
-            cont += item.critics as f32;
+        match previous {
+            Some(previous) => uncount_rating(&mut course, previous.critics),
+            None => course.review_count += 1,
         }
-        course.rating = cont / (course.reviews.len() as f32);
+        count_rating(&mut course, critics);
+        self.save_review(course_id, &data);
 
         self.courses.insert(&course_id, &course);
+        self.index_listing(&course);
+        self.charge_storage(initial_storage_usage);
 
         data
     }
@@ -612,166 +1922,2424 @@ impl Contract {
     pub fn get_review(&self,
         course_id: i128, 
         user_id: AccountId
-    ) -> Vec<Review> {
-        let mut result: Vec<CoursesObject> = self.courses.iter().map(|(_k, v)| v).collect::<Vec<CoursesObject>>();
+    ) -> Option<Review> {
+        self.course_review(course_id, &user_id)
+    }
 
-        result = result.iter().filter(|x| x.id == course_id).map(|x| x.clone()).collect();
+    pub fn get_reviews(&self, course_id: i128, cursor: Option<String>, limit: Option<u64>, sort_by: Option<ReviewSort>) -> Page<Review> {
+        if self.courses.get(&course_id).is_none() {
+            env::panic(b"Artemis: Course does not exist");
+        }
 
-        let review: Vec<Review> = result[0].reviews.iter().filter(|x| x.user_id == user_id).map(|x| x.clone()).collect();
+        let mut reviews: Vec<Review> = self.course_reviews.get(&course_id)
+            .map(|x| x.values().filter(|x| !x.hidden).collect())
+            .unwrap_or_default();
+        match sort_by.unwrap_or(ReviewSort::Newest) {
+            ReviewSort::Newest => reviews.sort_by_key(|x| std::cmp::Reverse(x.created_at)),
+            ReviewSort::Oldest => reviews.sort_by_key(|x| x.created_at),
+            ReviewSort::MostHelpful => reviews.sort_by_key(|x| {
+                (std::cmp::Reverse(x.helpful_votes as i64 - x.unhelpful_votes as i64), std::cmp::Reverse(x.created_at))
+            }),
+        }
 
-        review
+        offset_page(reviews, cursor, limit, |x| Some(x.clone()))
     }
 
-    fn profile_inscription(&mut self, course_id: i128) {
-        let indexaux = self.profiles.iter().position(|x| x.user_id == env::signer_account_id());//.expect("Category does not exist");
-
-        if indexaux.is_some() {
-            let index = self.profiles.iter().position(|x| x.user_id == env::signer_account_id()).expect("Profile does not exist");
-            self.profiles[index].user_id = env::signer_account_id().to_string();
-            let course = CoursePurchased {
-                course_id: course_id,
-                pass_certification: false,
-            };
-            self.profiles[index].purchased_courses.push(course);
-        } else {
-            let course = CoursePurchased {
-                course_id: course_id,
-                pass_certification: false,
-            };
-            let data = ProfileObject {
-                user_id: env::signer_account_id().to_string(),
-                purchased_courses: vec![course],
-            };
-            
-            self.profiles.push(data.clone());
-            env::log(b"profile and course purchased Created");
+    // One public reply per review, posting again replaces it
+    pub fn reply_review(&mut self, course_id: i128, reviewer: AccountId, reply: String) -> Review {
+        self.assert_not_paused(PauseScope::Reviews);
+        let initial_storage_usage = env::storage_usage();
+        self.creator_course(course_id);
+        if reply.is_empty() || reply.chars().count() > MAX_REVIEW_LENGTH {
+            env::panic(b"Artemis: Invalid reply");
+        }
+        let mut review = self.course_review(course_id, &reviewer).expect("Artemis: Review does not exist");
+        if review.reply.as_ref().is_some_and(|x| x.payer_id != env::predecessor_account_id()) {
+            env::panic(b"Artemis: Reply must be edited from the account that paid for it");
         }
-    }
 
-    pub fn get_courses_rating(&self, top: Option<i32>) -> Vec<MarketView> {
-        let top_limit = top.unwrap_or(12);
+        review.reply = Some(ReviewReply {
+            reply,
+            replied_at: env::block_timestamp(),
+            payer_id: env::predecessor_account_id(),
+        });
+        self.save_review(course_id, &review);
+        self.charge_storage(initial_storage_usage);
 
-        let mut top_courses: Vec<CoursesObject> = self.courses.iter()
-                                                .filter(|(_k, v)| v.rating > 0.0)
-                                                .map(|(_k, v)| v).collect::<Vec<CoursesObject>>();
+        log_event("review_replied", json!({
+            "course_id": course_id,
+            "reviewer_id": reviewer,
+        }));
 
-        top_courses.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap());
-        
-        top_courses.iter()
-        .take(top_limit as usize)
-        .map(|x| MarketView {
-            id: x.id,
-            creator_id: x.creator_id.to_string(),
-            title: x.title.to_string(),
-            categories: x.categories.clone(),
-            short_description: x.short_description.to_string(),
-            long_description: x.long_description.to_string(),
-            content: x.content.iter().map(|x| TemplateView {
-                title: x.title.to_string(),
-                tipo: x.tipo,
-            }).collect(),
-            img: x.img.to_string(),
-            price: x.price,
-            price_certification: x.price_certification,
-            rating: x.rating,
-            reviews: x.reviews.clone(),
-        }).collect()
+        review
     }
 
-}
+    // Each learner holds a single vote per review; voting again switches it
+    pub fn vote_review(&mut self, course_id: i128, reviewer: AccountId, helpful: bool) -> Review {
+        self.assert_not_paused(PauseScope::Reviews);
+        let initial_storage_usage = env::storage_usage();
+        let signer = env::signer_account_id();
 
-fn refund_deposit(storage_used: u64, extra_spend: Balance) {
-    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+        if !self.is_enrolled(course_id, &signer) {
+            env::panic(b"Artemis: Only enrolled learners can vote reviews");
+        }
+        if signer == reviewer {
+            env::panic(b"Artemis: Cannot vote your own review");
+        }
+        let mut review = self.course_review(course_id, &reviewer).expect("Artemis: Review does not exist");
+
+        let mut votes = self.review_votes.get(&course_id).unwrap_or_else(|| {
+            UnorderedMap::new([b"hw".to_vec(), course_id.to_le_bytes().to_vec()].concat())
+        });
+        let key = (reviewer.to_string(), signer);
+        // Switching a vote keeps its size, so it stays with whoever paid for it
+        let payer_id = votes.get(&key).map(|x| x.payer_id).unwrap_or_else(env::predecessor_account_id);
+        match votes.insert(&key, &ReviewVote { helpful, payer_id }).map(|x| x.helpful) {
+            Some(previous) if previous == helpful => env::panic(b"Artemis: Review already voted"),
+            Some(true) => review.helpful_votes -= 1,
+            Some(false) => review.unhelpful_votes -= 1,
+            None => {}
+        }
+        self.review_votes.insert(&course_id, &votes);
+
+        if helpful {
+            review.helpful_votes += 1;
+        } else {
+            review.unhelpful_votes += 1;
+        }
+        self.save_review(course_id, &review);
+        self.charge_storage(initial_storage_usage);
+
+        review
+    }
+
+    pub fn report_review(&mut self, course_id: i128, reviewer: AccountId, reason: String) {
+        self.assert_not_paused(PauseScope::Reviews);
+        let initial_storage_usage = env::storage_usage();
+        let signer = env::signer_account_id();
+
+        if !self.is_enrolled(course_id, &signer) {
+            env::panic(b"Artemis: Only enrolled learners can report reviews");
+        }
+        if signer == reviewer {
+            env::panic(b"Artemis: Cannot report your own review");
+        }
+        if reason.is_empty() || reason.chars().count() > MAX_REVIEW_LENGTH {
+            env::panic(b"Artemis: Invalid report reason");
+        }
+        let review = self.course_review(course_id, &reviewer).expect("Artemis: Review does not exist");
+        if review.hidden {
+            env::panic(b"Artemis: Review is already hidden");
+        }
+
+        let key = (course_id, reviewer.to_string());
+        let mut reports = self.review_reports.get(&key).unwrap_or_default();
+        if reports.iter().any(|x| x.reporter_id == signer) {
+            env::panic(b"Artemis: Review already reported");
+        }
+        reports.push(ReviewReport {
+            reporter_id: signer.to_string(),
+            reason: reason.to_string(),
+            reported_at: env::block_timestamp(),
+            payer_id: env::predecessor_account_id(),
+        });
+        self.review_reports.insert(&key, &reports);
+        self.charge_storage(initial_storage_usage);
+
+        log_event("review_reported", json!({
+            "course_id": course_id,
+            "reviewer_id": reviewer,
+            "reporter_id": signer,
+            "reason": reason,
+        }));
+    }
+
+    // Hidden reviews stay stored but leave the rating aggregates and the market view
+    pub fn hide_review(&mut self, course_id: i128, reviewer: AccountId) -> Review {
+        self.set_review_hidden(course_id, reviewer, true)
+    }
+
+    // Restoring also dismisses pending reports against the review
+    pub fn restore_review(&mut self, course_id: i128, reviewer: AccountId) -> Review {
+        self.set_review_hidden(course_id, reviewer, false)
+    }
+
+    pub fn get_reported_reviews(&self, cursor: Option<String>, limit: Option<u64>) -> Page<ReportedReview> {
+        offset_page(self.review_reports.keys_as_vector().to_vec(), cursor, limit, |(course_id, reviewer)| {
+            Some(ReportedReview {
+                course_id: *course_id,
+                review: self.course_review(*course_id, reviewer)?,
+                reports: self.review_reports.get(&(*course_id, reviewer.to_string())).unwrap_or_default(),
+            })
+        })
+    }
+
+    fn set_review_hidden(&mut self, course_id: i128, reviewer: AccountId, hidden: bool) -> Review {
+        self.assert_moderator();
+        let mut course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        let mut review = self.course_review(course_id, &reviewer).expect("Artemis: Review does not exist");
+
+        if review.hidden != hidden {
+            self.unindex_listing(&course);
+            if hidden {
+                uncount_rating(&mut course, review.critics);
+            } else {
+                count_rating(&mut course, review.critics);
+            }
+            review.hidden = hidden;
+            self.save_review(course_id, &review);
+            self.courses.insert(&course_id, &course);
+            self.index_listing(&course);
+        }
+        self.remove_review_reports(course_id, &reviewer);
+
+        log_event(if hidden { "review_hidden" } else { "review_restored" }, json!({
+            "course_id": course_id,
+            "reviewer_id": reviewer,
+            "moderator_id": env::signer_account_id(),
+        }));
+
+        review
+    }
+
+    fn storage_balance(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.storage_accounts.get(account_id).map(|account| StorageBalance {
+            total: U128(account.deposit),
+            available: U128(account.deposit - storage_cost(account.used_bytes)),
+        })
+    }
+
+    fn measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = "a".repeat(64);
+        self.storage_accounts.insert(&tmp_account_id, &StorageAccount { deposit: 0, used_bytes: 0 });
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.storage_accounts.remove(&tmp_account_id);
+    }
+
+    // Charges the predecessor for the bytes written since `initial_storage_usage`
+    fn charge_storage(&mut self, initial_storage_usage: u64) {
+        let bytes = env::storage_usage() as i64 - initial_storage_usage as i64;
+        self.pay_storage(bytes);
+    }
+
+    // An attached deposit tops up the caller's storage balance for this call, registering the
+    // account if needed, and whatever the new bytes don't use is refunded. As with the NEP-145
+    // methods the caller is the predecessor, the account the deposit came from
+    fn pay_storage(&mut self, bytes: i64) {
+        let account_id = env::predecessor_account_id();
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit > 0 {
+            let mut account = self.storage_accounts.get(&account_id).unwrap_or(StorageAccount {
+                deposit: 0,
+                used_bytes: self.account_storage_usage,
+            });
+            account.deposit += attached_deposit;
+            self.storage_accounts.insert(&account_id, &account);
+        }
+
+        self.settle_storage(&account_id, bytes);
+
+        if attached_deposit > 0 {
+            let mut account = self.storage_accounts.get(&account_id).expect("Artemis: Account is not registered for storage");
+            let refund = attached_deposit.min(account.deposit - storage_cost(account.used_bytes));
+            if refund > 0 {
+                account.deposit -= refund;
+                self.storage_accounts.insert(&account_id, &account);
+                Promise::new(account_id).transfer(refund);
+            }
+        }
+    }
+
+    // Growth is paid from the account's storage balance, freed bytes are refunded to the account
+    fn settle_storage(&mut self, account_id: &AccountId, bytes: i64) {
+        let mut account = match self.storage_accounts.get(account_id) {
+            Some(account) => account,
+            None if bytes > 0 => env::panic(b"Artemis: Account is not registered for storage"),
+            None => return,
+        };
+
+        let used_bytes = (account.used_bytes as i64 + bytes).max(self.account_storage_usage as i64) as u64;
+        let required = storage_cost(used_bytes);
+        assert!(
+            required <= account.deposit,
+            "Artemis: storage balance is less than required : {}",
+            required
+        );
+
+        let freed = storage_cost(account.used_bytes.saturating_sub(used_bytes));
+        account.used_bytes = used_bytes;
+        account.deposit -= freed;
+        self.storage_accounts.insert(account_id, &account);
+        if freed > 0 {
+            Promise::new(account_id.to_string()).transfer(freed);
+        }
+    }
+
+    fn is_enrolled(&self, course_id: i128, user_id: &AccountId) -> bool {
+        self.course_inscriptions.get(&course_id).is_some_and(|x| x.contains(user_id))
+    }
+
+    fn enrolled_users(&self, course_id: i128) -> Vec<AccountId> {
+        self.course_inscriptions.get(&course_id).map(|x| x.to_vec()).unwrap_or_default()
+    }
+
+    fn course_review(&self, course_id: i128, user_id: &AccountId) -> Option<Review> {
+        self.course_reviews.get(&course_id)?.get(user_id)
+    }
+
+    fn save_review(&mut self, course_id: i128, review: &Review) {
+        let mut reviews = self.course_reviews.get(&course_id).unwrap_or_else(|| {
+            UnorderedMap::new([b"rw".to_vec(), course_id.to_le_bytes().to_vec()].concat())
+        });
+        reviews.insert(&review.user_id, review);
+        self.course_reviews.insert(&course_id, &reviews);
+    }
+
+    // Drafts are edited in place, as their only version hasn't been sold yet. Once a course has
+    // been submitted every edit cuts a new version, so recorded versions never change
+    fn save_course_content(&mut self, course: &mut CoursesObject, content: Vec<SectionObject>, changelog: &str) {
+        let initial_storage_usage = env::storage_usage();
+        if course.status == CourseStatus::Draft {
+            log_lesson_hash_changes(course.id, course.content_version, &course.content, &content);
+            course.content = content;
+            course.updated_at = env::block_timestamp();
+            self.courses.insert(&course.id, course);
+            let mut version = self.content_versions.get(&(course.id, course.content_version)).expect("Artemis: Content version does not exist");
+            version.content = course.content.clone();
+            self.content_versions.insert(&(course.id, course.content_version), &version);
+        } else {
+            self.add_content_version(course, content, changelog.to_string());
+        }
+        self.charge_storage(initial_storage_usage);
+    }
+
+    fn add_content_version(&mut self, course: &mut CoursesObject, content: Vec<SectionObject>, changelog: String) -> ContentVersion {
+        course.content_version += 1;
+        log_lesson_hash_changes(course.id, course.content_version, &course.content, &content);
+        course.content = content;
+        course.updated_at = env::block_timestamp();
+
+        let version = ContentVersion {
+            version: course.content_version,
+            created_at: env::block_timestamp(),
+            changelog,
+            img: course.img.clone(),
+            content: course.content.clone(),
+        };
+        self.content_versions.insert(&(course.id, version.version), &version);
+        self.courses.insert(&course.id, course);
+        version
+    }
+
+    // Freed storage goes back to whoever paid for it: learners for their enrollment, the payer
+    // recorded on each key envelope, review, reply, vote and report, and the creator for the
+    // course itself. The first entry of each nested collection also paid for the collection
+    fn remove_course_data(&mut self, course: &CoursesObject) {
+        if let Some(mut votes) = self.review_votes.get(&course.id) {
+            let keys: Vec<(AccountId, AccountId)> = votes.keys().collect();
+            let mut first_payer = None;
+            for key in keys.iter() {
+                let initial_storage_usage = env::storage_usage();
+                let vote = votes.remove(key).expect("Artemis: Vote does not exist");
+                self.settle_storage(&vote.payer_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+                first_payer.get_or_insert(vote.payer_id);
+            }
+            let initial_storage_usage = env::storage_usage();
+            self.review_votes.remove(&course.id);
+            if let Some(payer_id) = first_payer {
+                self.settle_storage(&payer_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+            }
+        }
+
+        if let Some(mut reviews) = self.course_reviews.get(&course.id) {
+            let reviewers: Vec<AccountId> = reviews.keys().collect();
+            let mut first_payer = None;
+            for reviewer in reviewers.iter() {
+                self.remove_review_reports(course.id, reviewer);
+                let initial_storage_usage = env::storage_usage();
+                let review = reviews.remove(reviewer).expect("Artemis: Review does not exist");
+                let reply_bytes = serialized_len(&review.reply) - serialized_len(&None::<ReviewReply>);
+                if let Some(reply) = review.reply.as_ref() {
+                    self.settle_storage(&reply.payer_id, -reply_bytes);
+                }
+                self.settle_storage(&review.payer_id, env::storage_usage() as i64 - initial_storage_usage as i64 + reply_bytes);
+                first_payer.get_or_insert(review.payer_id);
+            }
+            let initial_storage_usage = env::storage_usage();
+            self.course_reviews.remove(&course.id);
+            if let Some(payer_id) = first_payer {
+                self.settle_storage(&payer_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+            }
+        }
+
+        // Enrollments were paid with the purchase deposit, so their storage is refunded directly
+        if let Some(mut inscriptions) = self.course_inscriptions.get(&course.id) {
+            let learners: Vec<AccountId> = inscriptions.to_vec();
+            let mut refunds: Vec<(AccountId, i64)> = Vec::new();
+            for user_id in learners.iter() {
+                let initial_storage_usage = env::storage_usage();
+                if let Some(envelope) = self.key_envelopes.remove(&(course.id, user_id.to_string())) {
+                    self.settle_storage(&envelope.payer_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+                }
+
+                let initial_storage_usage = env::storage_usage();
+                inscriptions.remove(user_id);
+                let mut bytes = env::storage_usage() as i64 - initial_storage_usage as i64;
+                if let Some(profile) = self.profiles.iter_mut().find(|x| x.user_id == *user_id) {
+                    if let Some(index) = profile.purchased_courses.iter().position(|k| k.course_id == course.id) {
+                        bytes -= serialized_len(&profile.purchased_courses.remove(index));
+                    }
+                }
+                refunds.push((user_id.to_string(), bytes));
+            }
+            let initial_storage_usage = env::storage_usage();
+            self.course_inscriptions.remove(&course.id);
+            if let Some((_, bytes)) = refunds.first_mut() {
+                *bytes += env::storage_usage() as i64 - initial_storage_usage as i64;
+            }
+            for (user_id, bytes) in refunds {
+                let refund = storage_cost((-bytes).max(0) as u64);
+                if refund > 0 {
+                    Promise::new(user_id).transfer(refund);
+                }
+            }
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        for version in 1..=course.content_version {
+            self.content_versions.remove(&(course.id, version));
+        }
+        self.key_services.remove(&course.id);
+        self.pending_courses.remove(&course.id);
+        self.unindex_course(course);
+        self.unindex_listing(course);
+        self.courses.remove(&course.id);
+        self.settle_storage(&course.creator_id, env::storage_usage() as i64 - initial_storage_usage as i64);
+    }
+
+    // Each report's payer paid for it, the first one also for the entry holding them
+    fn remove_review_reports(&mut self, course_id: i128, reviewer: &AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        let reports = match self.review_reports.remove(&(course_id, reviewer.to_string())) {
+            Some(reports) => reports,
+            None => return,
+        };
+        let mut bytes = env::storage_usage() as i64 - initial_storage_usage as i64;
+        for report in reports.iter().skip(1) {
+            let report_bytes = serialized_len(report);
+            self.settle_storage(&report.payer_id, -report_bytes);
+            bytes += report_bytes;
+        }
+        self.settle_storage(&reports[0].payer_id, bytes);
+    }
+
+    fn assert_category_exists(&self, category_id: i128) {
+        if !self.categories.iter().any(|x| x.id == category_id) {
+            env::panic(b"Category does not exist");
+        }
+    }
+
+    fn valid_category_slug(&self, slug: &str, category_id: Option<i128>) -> String {
+        let slug = normalize_tag(slug);
+        if slug.is_empty() || !slug.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            env::panic(b"Artemis: Category slug must be alphanumeric with dashes");
+        }
+        if self.categories.iter().any(|x| x.slug == slug && Some(x.id) != category_id) {
+            env::panic(b"Artemis: Category slug already exists");
+        }
+        slug
+    }
+
+    // The category itself plus every category below it
+    fn category_descendants(&self, category_id: i128) -> Vec<i128> {
+        let mut result = vec![category_id];
+        let mut i = 0;
+        while i < result.len() {
+            let current = result[i];
+            result.extend(self.categories.iter().filter(|x| x.parent_id == Some(current)).map(|x| x.id));
+            i += 1;
+        }
+        result
+    }
+
+    fn category_nodes(&self, parent_id: Option<i128>) -> Vec<CategoryNode> {
+        let mut children: Vec<&CategoriesJson> = self.categories.iter().filter(|x| x.parent_id == parent_id).collect();
+        children.sort_by_key(|x| (x.order, x.id));
+        children.iter().map(|x| CategoryNode {
+            category: (*x).clone(),
+            children: self.category_nodes(Some(x.id)),
+        }).collect()
+    }
+
+    fn valid_category_ids(&self, mut category_ids: Vec<i128>) -> Vec<i128> {
+        category_ids.sort_unstable();
+        category_ids.dedup();
+        if category_ids.is_empty() {
+            env::panic(b"Artemis: Courses need at least one category");
+        }
+        assert!(
+            category_ids.len() <= self.max_categories as usize,
+            "Artemis: Courses can have at most {} categories",
+            self.max_categories
+        );
+        category_ids.iter().for_each(|x| self.assert_category_exists(*x));
+        category_ids
+    }
+
+    fn index_course(&mut self, course: &CoursesObject) {
+        let mut ids = self.courses_by_creator.get(&course.creator_id).unwrap_or_else(|| {
+            UnorderedSet::new([b"us".to_vec(), course.creator_id.as_bytes().to_vec()].concat())
+        });
+        ids.insert(&course.id);
+        self.courses_by_creator.insert(&course.creator_id, &ids);
+        for category_id in course.category_ids.iter() {
+            let mut ids = self.courses_by_category.get(category_id).unwrap_or_else(|| {
+                UnorderedSet::new([b"cs".to_vec(), category_id.to_le_bytes().to_vec()].concat())
+            });
+            ids.insert(&course.id);
+            self.courses_by_category.insert(category_id, &ids);
+        }
+        for tag in course.tags.iter() {
+            let mut ids = self.courses_by_tag.get(tag).unwrap_or_else(|| {
+                UnorderedSet::new([b"ts".to_vec(), tag.as_bytes().to_vec()].concat())
+            });
+            ids.insert(&course.id);
+            self.courses_by_tag.insert(tag, &ids);
+        }
+    }
+
+    fn unindex_course(&mut self, course: &CoursesObject) {
+        if let Some(mut ids) = self.courses_by_creator.get(&course.creator_id) {
+            ids.remove(&course.id);
+            if ids.is_empty() {
+                self.courses_by_creator.remove(&course.creator_id);
+            } else {
+                self.courses_by_creator.insert(&course.creator_id, &ids);
+            }
+        }
+        for category_id in course.category_ids.iter() {
+            if let Some(mut ids) = self.courses_by_category.get(category_id) {
+                ids.remove(&course.id);
+                if ids.is_empty() {
+                    self.courses_by_category.remove(category_id);
+                } else {
+                    self.courses_by_category.insert(category_id, &ids);
+                }
+            }
+        }
+        for tag in course.tags.iter() {
+            if let Some(mut ids) = self.courses_by_tag.get(tag) {
+                ids.remove(&course.id);
+                if ids.is_empty() {
+                    self.courses_by_tag.remove(tag);
+                } else {
+                    self.courses_by_tag.insert(tag, &ids);
+                }
+            }
+        }
+    }
+
+    // Market listings: published courses only, keyed by publish time, by rating and by search token
+    fn index_listing(&mut self, course: &CoursesObject) {
+        if course.status != CourseStatus::Published {
+            return;
+        }
+        self.courses_by_publish_time.insert(&(course.published_at.unwrap_or_default(), course.id), &());
+        if course.rating_count > 0 {
+            self.courses_by_rating.insert(&rating_key(course), &());
+        }
+        for token in course_tokens(course) {
+            let mut ids = self.courses_by_token.get(&token).unwrap_or_else(|| {
+                UnorderedSet::new([b"ws".to_vec(), token.as_bytes().to_vec()].concat())
+            });
+            ids.insert(&course.id);
+            self.courses_by_token.insert(&token, &ids);
+        }
+    }
+
+    fn unindex_listing(&mut self, course: &CoursesObject) {
+        self.courses_by_publish_time.remove(&(course.published_at.unwrap_or_default(), course.id));
+        self.courses_by_rating.remove(&rating_key(course));
+        for token in course_tokens(course) {
+            if let Some(mut ids) = self.courses_by_token.get(&token) {
+                ids.remove(&course.id);
+                if ids.is_empty() {
+                    self.courses_by_token.remove(&token);
+                } else {
+                    self.courses_by_token.insert(&token, &ids);
+                }
+            }
+        }
+    }
+
+    // Published course ids matching the creator/category/tag indexes, in publish order.
+    // Returns None when no filter is given so callers can walk the publish time index instead
+    fn listed_course_ids(&self, creator_id: Option<AccountId>, category_id: Option<i128>, tag: Option<String>) -> Option<Vec<i128>> {
+        let by_creator = creator_id.map(|x| self.courses_by_creator.get(&x).map(|ids| ids.to_vec()).unwrap_or_default());
+        let by_category = category_id.map(|x| self.category_descendants(x).iter()
+            .flat_map(|id| self.courses_by_category.get(id).map(|ids| ids.to_vec()).unwrap_or_default())
+            .collect::<Vec<i128>>());
+        let by_tag = tag.map(|x| self.courses_by_tag.get(&normalize_tag(&x)).map(|ids| ids.to_vec()).unwrap_or_default());
+
+        let mut filters: Vec<Vec<i128>> = vec![by_creator, by_category, by_tag].into_iter().flatten().collect();
+        filters.sort_by_key(|x| x.len());
+        let mut ids = filters.first()?.clone();
+        ids.retain(|id| filters.iter().all(|x| x.contains(id)));
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut listed: Vec<(u64, i128)> = ids.into_iter()
+            .filter_map(|id| self.courses.get(&id))
+            .filter(|x| x.status == CourseStatus::Published)
+            .map(|x| (x.published_at.unwrap_or_default(), x.id))
+            .collect();
+        listed.sort_unstable();
+        Some(listed.into_iter().map(|(_, id)| id).collect())
+    }
+
+    // Categories are resolved at read time so admin edits show up on every course
+    fn market_view(&self, x: &CoursesObject) -> MarketView {
+        MarketView {
+            id: x.id,
+            creator_id: x.creator_id.to_string(),
+            title: x.title.to_string(),
+            categories: x.category_ids.iter().map(|id| self.categories.iter().find(|c| c.id == *id).expect("Category does not exist").clone()).collect(),
+            tags: x.tags.clone(),
+            short_description: x.short_description.to_string(),
+            long_description: x.long_description.to_string(),
+            img: x.img.clone(),
+            content: x.content.iter().map(section_view).collect(),
+            content_version: x.content_version,
+            price: x.price,
+            price_certification: x.price_certification,
+            certification_validity_ns: x.certification_validity_ns,
+            rating: average_rating(x),
+            rating_count: x.rating_count,
+            rating_histogram: x.rating_histogram,
+        }
+    }
+
+    // The predecessor, so a contract the owner merely signed a call through can't manage upgrades
+    fn assert_owner(&self) {
+        if env::predecessor_account_id() != self.owner_id {
+            env::panic(b"Artemis: Only the owner can manage upgrades");
+        }
+    }
+
+    fn upgrade_delay(&self) -> u64 {
+        match &self.staged_upgrade_delay {
+            Some(staged) if env::block_timestamp() >= staged.available_at => staged.delay_ns,
+            _ => self.upgrade_delay_ns,
+        }
+    }
+
+    fn assert_owner_or_admin(&self) {
+        let signer = env::signer_account_id();
+        if signer != self.owner_id && !self.administrators.contains(&signer) {
+            env::panic(b"Artemis: Only the owner or administrators can pause the contract");
+        }
+    }
+
+    fn assert_not_paused(&self, scope: PauseScope) {
+        if self.paused_scopes.contains(&PauseScope::All) || self.paused_scopes.contains(&scope) {
+            env::panic(format!("Artemis: {:?} is paused", scope).as_bytes());
+        }
+    }
+
+    fn assert_moderator(&self) {
+        let signer = env::signer_account_id();
+        if !self.moderators.contains(&signer) && !self.administrators.contains(&signer) {
+            env::panic(b"Only moderators can review courses");
+        }
+    }
+
+    fn creator_courses(&self, creator_id: &AccountId) -> Vec<CoursesObject> {
+        let mut ids = self.courses_by_creator.get(creator_id).map(|x| x.to_vec()).unwrap_or_default();
+        ids.sort_unstable();
+        ids.iter().filter_map(|id| self.courses.get(id)).collect()
+    }
+
+    fn creator_course(&self, course_id: i128) -> CoursesObject {
+        let course = self.courses.get(&course_id).expect("Artemis: Course does not exist");
+        if course.creator_id != env::signer_account_id() {
+            env::panic(b"No permission");
+        }
+        course
+    }
+
+    // Returns the bytes added to the profiles kept in the contract state
+    fn profile_inscription(&mut self, course_id: i128, purchase_price: Balance) -> i64 {
+        let indexaux = self.profiles.iter().position(|x| x.user_id == env::signer_account_id());//.expect("Category does not exist");
+
+        if indexaux.is_some() {
+            let index = self.profiles.iter().position(|x| x.user_id == env::signer_account_id()).expect("Profile does not exist");
+            self.profiles[index].user_id = env::signer_account_id().to_string();
+            let course = CoursePurchased {
+                course_id,
+                pass_certification: false,
+                certification_expires_at: None,
+                certification_revoked: false,
+                certified_version: None,
+                purchase_price,
+                purchased_at: env::block_timestamp(),
+                certified_at: None,
+            };
+            let bytes = serialized_len(&course);
+            self.profiles[index].purchased_courses.push(course);
+            bytes
+        } else {
+            let course = CoursePurchased {
+                course_id,
+                pass_certification: false,
+                certification_expires_at: None,
+                certification_revoked: false,
+                certified_version: None,
+                purchase_price,
+                purchased_at: env::block_timestamp(),
+                certified_at: None,
+            };
+            let data = ProfileObject {
+                user_id: env::signer_account_id().to_string(),
+                purchased_courses: vec![course],
+            };
+            
+            self.profiles.push(data.clone());
+            env::log(b"profile and course purchased Created");
+            serialized_len(&data)
+        }
+    }
+
+    pub fn get_courses_rating(&self, cursor: Option<String>, limit: Option<u64>) -> Page<MarketView> {
+        let limit = limit.unwrap_or(12) as usize;
+        assert_ne!(limit, 0, "Cannot provide limit of 0.");
+
+        let keys: Box<dyn Iterator<Item = ((u32, i128), ())>> = match cursor.map(|x| parse_cursor(&x)) {
+            Some((rating, id)) => Box::new(self.courses_by_rating.iter_rev_from((rating as u32, id))),
+            None => Box::new(self.courses_by_rating.iter_rev()),
+        };
+        let keys: Vec<(u32, i128)> = keys.take(limit).map(|(key, _)| key).collect();
+
+        Page {
+            items: keys.iter().filter_map(|(_, id)| self.courses.get(&-id)).map(|x| self.market_view(&x)).collect(),
+            total: self.courses_by_rating.len(),
+            next_cursor: if keys.len() == limit { keys.last().map(|(rating, id)| format!("{}:{}", rating, id)) } else { None },
+        }
+    }
+
+}
+
+fn count_rating(course: &mut CoursesObject, critics: i8) {
+    course.rating_count += 1;
+    course.rating_sum += critics as u64;
+    course.rating_histogram[(critics - 1) as usize] += 1;
+}
+
+fn uncount_rating(course: &mut CoursesObject, critics: i8) {
+    course.rating_count -= 1;
+    course.rating_sum -= critics as u64;
+    course.rating_histogram[(critics - 1) as usize] -= 1;
+}
+
+fn average_rating(course: &CoursesObject) -> u32 {
+    if course.rating_count == 0 {
+        return 0;
+    }
+    (course.rating_sum * RATING_PRECISION / course.rating_count) as u32
+}
+
+// Average pulled towards the prior, so a course needs several good reviews to rank high
+fn bayesian_rating(course: &CoursesObject) -> u32 {
+    let sum = RATING_PRIOR_MEAN * RATING_PRIOR_WEIGHT + course.rating_sum;
+    (sum * RATING_PRECISION / (RATING_PRIOR_WEIGHT + course.rating_count)) as u32
+}
+
+// Highest Bayesian rating first; among equal ratings the older course (lower id) comes first when iterating in reverse
+fn rating_key(course: &CoursesObject) -> (u32, i128) {
+    (bayesian_rating(course), -course.id)
+}
+
+type SortedCourse = ((u128, i128), CoursesObject);
+
+// Offset based page for views without a sorted index, the cursor is the next offset
+fn offset_page<T, U>(items: Vec<T>, cursor: Option<String>, limit: Option<u64>, load: impl Fn(&T) -> Option<U>) -> Page<U> {
+    let start = parse_offset(cursor);
+    let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+    assert_ne!(limit, 0, "Cannot provide limit of 0.");
+
+    let end = start.saturating_add(limit).min(items.len());
+    let page: Vec<U> = items.iter().skip(start).take(limit).filter_map(load).collect();
+
+    Page {
+        items: page,
+        total: items.len() as u64,
+        next_cursor: if end < items.len() { Some(end.to_string()) } else { None },
+    }
+}
+
+fn parse_offset(cursor: Option<String>) -> usize {
+    match cursor {
+        Some(cursor) => cursor.parse().unwrap_or_else(|_| env::panic(b"Artemis: Invalid cursor")),
+        None => 0,
+    }
+}
+
+// Ties are broken by course id so every course has a unique position
+fn market_sort_key(course: &CoursesObject, sort_by: SortBy) -> (u128, i128) {
+    match sort_by {
+        SortBy::Newest | SortBy::Oldest => (course.published_at.unwrap_or_default() as u128, course.id),
+        SortBy::PriceAsc | SortBy::PriceDesc => (course.price, course.id),
+        SortBy::Rating => (average_rating(course) as u128, course.id),
+        SortBy::Enrollments => (course.enrollment_count as u128, course.id),
+        SortBy::Reviews => (course.rating_count as u128, course.id),
+    }
+}
+
+fn parse_cursor(cursor: &str) -> (u128, i128) {
+    let mut parts = cursor.splitn(2, ':');
+    let key = parts.next().and_then(|x| x.parse().ok());
+    let id = parts.next().and_then(|x| x.parse().ok());
+    match (key, id) {
+        (Some(key), Some(id)) => (key, id),
+        _ => env::panic(b"Artemis: Invalid cursor"),
+    }
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<&str>>().join("-").to_lowercase()
+}
+
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().map(|x| normalize_tag(x)).filter(|x| !x.is_empty()).collect();
+    tags.sort();
+    tags.dedup();
+    assert!(tags.len() <= MAX_TAGS, "Artemis: Courses can have at most {} tags", MAX_TAGS);
+    if tags.iter().any(|x| x.len() > MAX_TAG_LENGTH) {
+        env::panic(b"Artemis: Tag is too long");
+    }
+    tags
+}
+
+// Lowercased words without stopwords or duplicates
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if word.is_empty() || STOPWORDS.contains(&word.as_str()) || tokens.contains(&word) {
+            continue;
+        }
+        tokens.push(word);
+    }
+    tokens
+}
+
+fn course_tokens(course: &CoursesObject) -> Vec<String> {
+    tokenize(&format!("{} {}", course.title, course.short_description))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn is_sha256_hex(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn assert_valid_lesson(lesson: &TemplateObject) {
+    let reference = &lesson.content;
+    if reference.locator.is_empty() {
+        env::panic(b"Artemis: Lesson content locator can't be empty");
+    }
+    if !is_sha256_hex(&reference.content_hash) {
+        env::panic(b"Artemis: Lesson content hash must be a hex sha256");
+    }
+    if reference.media_type.is_empty() {
+        env::panic(b"Artemis: Lesson media type can't be empty");
+    }
+}
+
+fn assert_valid_media(media: &MediaObject) {
+    if !is_sha256_hex(&media.sha256) {
+        env::panic(b"Artemis: Media hash must be a hex sha256");
+    }
+    if media.media_type.is_empty() {
+        env::panic(b"Artemis: Media type can't be empty");
+    }
+}
+
+fn lessons(content: &[SectionObject]) -> impl Iterator<Item = &TemplateObject> {
+    content.iter().flat_map(|x| x.lessons.iter())
+}
+
+// Lessons are compared by position, so adding, moving or removing one reports every shifted hash
+fn log_lesson_hash_changes(course_id: i128, version: u32, previous: &[SectionObject], content: &[SectionObject]) {
+    let previous_hashes: Vec<String> = lessons(previous).map(|x| x.content.content_hash.to_string()).collect();
+    let changed_lessons: Vec<Value> = lessons(content).enumerate()
+        .filter(|(i, x)| previous_hashes.get(*i) != Some(&x.content.content_hash))
+        .map(|(i, x)| json!({
+            "lesson_idx": i,
+            "old_hash": previous_hashes.get(i),
+            "new_hash": x.content.content_hash,
+        })).collect();
+
+    if !changed_lessons.is_empty() {
+        log_event("lesson_hash_updated", json!({
+            "course_id": course_id,
+            "version": version,
+            "lessons": changed_lessons,
+        }));
+    }
+}
+
+// NEP-297 style event log
+fn log_event(event: &str, data: Value) {
+    let log = json!({
+        "standard": "artemis",
+        "version": "1.0.0",
+        "event": event,
+        "data": [data],
+    });
+    env::log(format!("EVENT_JSON:{}", log).as_bytes());
+}
+
+fn assert_valid_section(section: &SectionObject) {
+    section.lessons.iter().for_each(assert_valid_lesson);
+}
+
+fn section_view(section: &SectionObject) -> SectionView {
+    SectionView {
+        title: section.title.to_string(),
+        lessons: section.lessons.iter().map(|x| TemplateView {
+            title: x.title.to_string(),
+            lesson_type: x.lesson_type.clone(),
+            free_preview: x.free_preview,
+        }).collect(),
+    }
+}
+
+fn certification_status(purchased: &CoursePurchased) -> CertificationStatus {
+    if purchased.certification_revoked {
+        return CertificationStatus::Revoked;
+    }
+    if !purchased.pass_certification {
+        return CertificationStatus::NotCertified;
+    }
+    match purchased.certification_expires_at {
+        Some(expires_at) if expires_at <= env::block_timestamp() => CertificationStatus::Expired,
+        _ => CertificationStatus::Valid,
+    }
+}
+
+fn storage_cost(bytes: u64) -> Balance {
+    env::storage_byte_cost() * Balance::from(bytes)
+}
+
+// Size of a value kept in the contract state itself, where storage_usage only changes once the state is written
+fn serialized_len<T: BorshSerialize>(value: &T) -> i64 {
+    value.try_to_vec().expect("Artemis: Cannot serialize value").len() as i64
+}
+
+// Storage is paid by the signer, who is also the account being enrolled or certified
+fn refund_deposit(storage_used: i64, extra_spend: Balance) {
+    let required_cost = storage_cost(storage_used.max(0) as u64);
     let attached_deposit = env::attached_deposit() - extra_spend;
 
-    assert!(
-        required_cost <= attached_deposit,
-        "Must attach {} yoctoNEAR to cover storage",
-        required_cost,
-    );
+    assert!(
+        required_cost <= attached_deposit,
+        "Must attach {} yoctoNEAR to cover storage",
+        required_cost,
+    );
+
+    let refund = attached_deposit - required_cost;
+    if refund > 1 {
+        Promise::new(env::signer_account_id()).transfer(refund);
+    }
+}
+
+// unlike the struct's functions above, this function cannot use attributes #[derive(…)] or #[near_bindgen]
+// any attempts will throw helpful warnings upon 'cargo build'
+// while this function cannot be invoked directly on the blockchain, it can be called from an invoked function
+
+/*
+ * the rest of this file sets up unit tests
+ * to run these, the command will be:
+ * cargo test --package rust-counter-tutorial -- --nocapture
+ * Note: 'rust-counter-tutorial' comes from cargo.toml's 'name' key
+ */
+
+// use the attribute below for unit tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    const ONE_YEAR_NS: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+    const STORAGE_DEPOSIT: Balance = 10u128.pow(24);
+
+    // part of writing unit tests is setting up a mock context
+    // this is also a useful list to peek at when wondering what's available in env::*
+    fn get_context(signer: &str, attached_deposit: Balance, block_timestamp: u64) -> VMContext {
+        VMContext {
+            current_account_id: "artemis.testnet".to_string(),
+            signer_account_id: signer.to_string(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id: signer.to_string(),
+            input: vec![],
+            block_index: 0,
+            block_timestamp,
+            account_balance: 10u128.pow(26),
+            account_locked_balance: 0,
+            storage_usage: 100_000,
+            attached_deposit,
+            prepaid_gas: 10u64.pow(18),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 19,
+        }
+    }
+
+    fn register(contract: &mut Contract, account_id: &str) {
+        testing_env!(get_context(account_id, STORAGE_DEPOSIT, 0));
+        contract.storage_deposit(None, None);
+    }
+
+    #[test]
+    fn delete_admin_removes_administrator() {
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        let mut contract = Contract::new(
+            ValidAccountId::try_from("e-learning.testnet").unwrap(),
+            ValidAccountId::try_from("vault.testnet").unwrap(),
+        );
+        contract.delete_admin("juanochando.testnet".to_string());
+        assert!(!contract.administrators.contains(&"juanochando.testnet".to_string()));
+    }
+
+    fn setup_contract() -> Contract {
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        let mut contract = Contract::new(
+            ValidAccountId::try_from("e-learning.testnet").unwrap(),
+            ValidAccountId::try_from("vault.testnet").unwrap(),
+        );
+        register(&mut contract, "creator.testnet");
+        register(&mut contract, "e-learning.testnet");
+        let category = contract.set_category("Programming".to_string(), "img".to_string(), None, 0, "programming".to_string());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.publish_course(
+            "Rust".to_string(),
+            vec![category.id],
+            vec!["Smart Contracts".to_string(), "rust".to_string()],
+            "short".to_string(),
+            "long".to_string(),
+            media("cd"),
+            vec![],
+            U128(100),
+            U128(1_000),
+            Some(U64(ONE_YEAR_NS)),
+        );
+        contract.submit_course(1);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.approve_course(1);
+        contract
+    }
+
+    fn media(hash_byte: &str) -> MediaObject {
+        MediaObject {
+            url: "https://cdn.artemis.testnet/cover.png".to_string(),
+            sha256: hash_byte.repeat(32),
+            media_type: "image/png".to_string(),
+        }
+    }
+
+    fn buy_and_certify(contract: &mut Contract, learner: &str, block_timestamp: u64) {
+        testing_env!(get_context(learner, 100 + STORAGE_DEPOSIT, block_timestamp));
+        contract.course_buy(1);
+        testing_env!(get_context(learner, 1_000 + STORAGE_DEPOSIT, block_timestamp));
+        contract.pass_certification_buy(1);
+    }
+
+    #[test]
+    fn certification_expires_after_validity() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 10);
+
+        let view = contract.get_certification_status("learner.testnet".to_string(), 1);
+        assert_eq!(view.status, CertificationStatus::Valid);
+        assert_eq!(view.expires_at.unwrap().0, 10 + ONE_YEAR_NS);
+
+        testing_env!(get_context("learner.testnet", 0, 10 + ONE_YEAR_NS));
+        let view = contract.get_certification_status("learner.testnet".to_string(), 1);
+        assert_eq!(view.status, CertificationStatus::Expired);
+    }
+
+    #[test]
+    fn renew_certification_extends_expiry() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        // Renewing early stacks on top of the remaining validity
+        testing_env!(get_context("learner.testnet", 700, ONE_YEAR_NS / 2));
+        let purchased = contract.renew_certification(1);
+        assert_eq!(purchased.certification_expires_at, Some(2 * ONE_YEAR_NS));
+
+        // Renewing after expiry starts again from the current block
+        testing_env!(get_context("learner.testnet", 700, 3 * ONE_YEAR_NS));
+        let purchased = contract.renew_certification(1);
+        assert_eq!(purchased.certification_expires_at, Some(4 * ONE_YEAR_NS));
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: attached deposit is less than price : 700")]
+    fn renew_certification_requires_discounted_price() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("learner.testnet", 699, 0));
+        contract.renew_certification(1);
+    }
+
+    #[test]
+    fn revoked_certification_is_reported() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.change_pass_certification("learner.testnet".to_string(), 1);
+
+        let view = contract.get_certification_status("learner.testnet".to_string(), 1);
+        assert_eq!(view.status, CertificationStatus::Revoked);
+        let view = contract.get_certification_status("other.testnet".to_string(), 1);
+        assert_eq!(view.status, CertificationStatus::NotCertified);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Certification has been revoked")]
+    fn revoked_certification_cannot_be_bought_again() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.change_pass_certification("learner.testnet".to_string(), 1);
+
+        testing_env!(get_context("learner.testnet", 1_000 + STORAGE_DEPOSIT, 0));
+        contract.pass_certification_buy(1);
+    }
+
+    fn lesson(title: &str, free_preview: bool) -> TemplateObject {
+        TemplateObject {
+            title: title.to_string(),
+            description: "description".to_string(),
+            content: ContentReference {
+                storage: ContentStorage::Ipfs,
+                locator: "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi".to_string(),
+                content_hash: "ab".repeat(32),
+                media_type: "video/mp4".to_string(),
+            },
+            lesson_type: LessonType::Video { duration_seconds: 600 },
+            free_preview,
+        }
+    }
+
+    #[test]
+    fn creator_edits_and_reorders_lessons() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.add_section(1, "Basics".to_string());
+        contract.add_section(1, "Advanced".to_string());
+        contract.add_lesson(1, 0, lesson("Ownership", false));
+        contract.add_lesson(1, 0, lesson("Borrowing", false));
+        contract.put_lesson(1, 0, 1, lesson("Borrowing & lifetimes", false));
+        let sections = contract.move_lesson(1, 0, 0, 1, 0);
+        assert_eq!(sections[0].lessons[0].title, "Borrowing & lifetimes");
+        assert_eq!(sections[1].lessons[0].title, "Ownership");
+
+        let sections = contract.delete_lesson(1, 1, 0);
+        assert!(sections[1].lessons.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "No permission")]
+    fn only_creator_can_add_lessons() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.add_section(1, "Basics".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Lesson is not a free preview")]
+    fn paid_lessons_are_not_previewable() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.add_section(1, "Basics".to_string());
+        contract.add_lesson(1, 0, lesson("Intro", true));
+        contract.add_lesson(1, 0, lesson("Ownership", false));
+        assert_eq!(contract.get_preview_lesson(1, 0, 0).content.content_hash, "ab".repeat(32));
+        contract.get_preview_lesson(1, 0, 1);
+    }
+
+    #[test]
+    fn content_updates_keep_previous_versions() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("creator.testnet", 0, 50));
+        let section = SectionObject {
+            title: "Basics".to_string(),
+            lessons: vec![lesson("Ownership", false)],
+        };
+        let version = contract.update_course_content(1, vec![section], None, "Add ownership lesson".to_string());
+        assert_eq!(version.version, 2);
+
+        let versions = contract.get_course_versions(1, None, None).items;
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[1].created_at.0, 50);
+        let page = contract.get_course_versions(1, Some("1".to_string()), Some(1));
+        assert_eq!((page.items[0].version, page.total, page.next_cursor), (2, 2, None));
+        assert!(contract.get_course_version("learner.testnet".to_string(), 1, 1).content.is_empty());
+        assert_eq!(contract.get_course_version("learner.testnet".to_string(), 1, 2).content.len(), 1);
+
+        let view = contract.get_certification_status("learner.testnet".to_string(), 1);
+        assert_eq!(view.certified_version, Some(1));
+    }
+
+    #[test]
+    fn key_envelopes_are_released_to_enrolled_learners() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+        register(&mut contract, "learner.testnet");
+        register(&mut contract, "keys.testnet");
+
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.register_learner_key("ed25519:learner".to_string());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_course_key_service(1, Some("keys.testnet".to_string()));
+        assert_eq!(contract.get_pending_key_envelopes(1, None, None).items.len(), 1);
+
+        testing_env!(get_context("keys.testnet", 0, 0));
+        contract.set_key_envelopes(1, vec![KeyEnvelopeInput {
+            user_id: "learner.testnet".to_string(),
+            encrypted_key: "sealed".to_string(),
+        }]);
+        assert!(contract.get_pending_key_envelopes(1, None, None).items.is_empty());
+
+        let envelope = contract.get_key_envelope("learner.testnet".to_string(), 1);
+        assert_eq!(envelope.encrypted_key, "sealed");
+        assert_eq!(envelope.learner_public_key, "ed25519:learner");
+
+        // Rotating the learner key queues a fresh envelope
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.register_learner_key("ed25519:rotated".to_string());
+        assert_eq!(contract.get_pending_key_envelopes(1, None, None).items.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: User is not enrolled in the course")]
+    fn key_envelopes_require_enrollment() {
+        let mut contract = setup_contract();
+        register(&mut contract, "outsider.testnet");
+
+        testing_env!(get_context("outsider.testnet", 0, 0));
+        contract.register_learner_key("ed25519:outsider".to_string());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_key_envelopes(1, vec![KeyEnvelopeInput {
+            user_id: "outsider.testnet".to_string(),
+            encrypted_key: "sealed".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn content_hashes_change_only_through_new_versions() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.add_section(1, "Basics".to_string());
+        contract.add_lesson(1, 0, lesson("Ownership", false));
+        assert!(contract.verify_lesson_hash(1, 0, "ab".repeat(32)));
+        assert!(contract.verify_img_hash(1, "cd".repeat(32)));
+
+        let mut updated = lesson("Ownership", false);
+        updated.content.content_hash = "ef".repeat(32);
+        let section = SectionObject {
+            title: "Basics".to_string(),
+            lessons: vec![updated],
+        };
+        contract.update_course_content(1, vec![section], Some(media("01")), "Re-encode video".to_string());
+
+        assert!(contract.verify_lesson_hash(1, 0, "EF".repeat(32)));
+        assert!(!contract.verify_lesson_hash(1, 0, "ab".repeat(32)));
+        assert!(contract.verify_img_hash(1, "01".repeat(32)));
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs.iter().any(|x| x.starts_with("EVENT_JSON:") && x.contains("lesson_hash_updated")));
+        assert!(logs.iter().any(|x| x.contains("course_img_hash_updated")));
+    }
+
+    #[test]
+    fn lesson_edits_cut_new_versions_once_submitted() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("creator.testnet", 0, 10));
+        contract.add_section(1, "Basics".to_string());
+        contract.add_lesson(1, 0, lesson("Ownership", false));
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs.iter().any(|x| x.contains("lesson_hash_updated") && x.contains("\"version\":3")));
+
+        let versions = contract.get_course_versions(1, None, None).items;
+        assert_eq!(versions.iter().map(|x| x.changelog.as_str()).collect::<Vec<&str>>(), vec!["Initial version", "Section added", "Lesson added"]);
+        assert!(contract.get_course_version("learner.testnet".to_string(), 1, 1).content.is_empty());
+        assert!(contract.get_course_version("learner.testnet".to_string(), 1, 2).content[0].lessons.is_empty());
+
+        contract.delete_lesson(1, 0, 0);
+        assert_eq!(contract.get_course_version("learner.testnet".to_string(), 1, 3).content[0].lessons.len(), 1);
+        assert_eq!(contract.courses.get(&1).unwrap().content_version, 4);
+    }
+
+    #[test]
+    fn drafts_are_edited_in_place() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.testnet", 0, 0));
+        let draft = contract.publish_course("Draft".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), media("cd"), vec![], U128(100), U128(1_000), None).id;
+        contract.add_section(draft, "Basics".to_string());
+        contract.add_lesson(draft, 0, lesson("Ownership", false));
+
+        assert_eq!(contract.courses.get(&draft).unwrap().content_version, 1);
+        assert_eq!(contract.content_versions.get(&(draft, 1)).unwrap().content[0].lessons.len(), 1);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|x| x.contains("lesson_hash_updated")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Lesson content can only change through update_course_content")]
+    fn put_lesson_cannot_swap_content() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.add_section(1, "Basics".to_string());
+        contract.add_lesson(1, 0, lesson("Ownership", false));
+
+        let mut swapped = lesson("Ownership", false);
+        swapped.content.content_hash = "ef".repeat(32);
+        contract.put_lesson(1, 0, 0, swapped);
+    }
+
+    #[test]
+    fn only_published_courses_reach_the_market() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.publish_course(
+            "Draft".to_string(),
+            vec![1],
+            vec![],
+            "short".to_string(),
+            "long".to_string(),
+            media("cd"),
+            vec![],
+            U128(100),
+            U128(1_000),
+            None,
+        );
+        assert_eq!(contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, None).items.len(), 1);
+        assert_eq!(contract.get_recent_courses(None, Some(10)).items.len(), 1);
+
+        contract.submit_course(2);
+        assert_eq!(contract.get_pending_courses(None, None).items.len(), 1);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.reject_course(2, "Missing lessons".to_string());
+        assert!(contract.get_pending_courses(None, None).items.is_empty());
+        assert_eq!(contract.get_courses_intructor(Some("creator.testnet".to_string()), None, None).total, 1);
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        assert_eq!(contract.get_draft_courses(None, None).items[0].rejection_reason, Some("Missing lessons".to_string()));
+        testing_env!(get_context("other.testnet", 0, 0));
+        assert!(contract.get_draft_courses(None, None).items.is_empty());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_course_status(1, CourseStatus::Unlisted);
+        assert!(contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, None).items.is_empty());
+        assert_eq!(contract.get_market_courses(Some(1), None, None, None, None, None, None, None, None, None, None).items.len(), 1);
+    }
+
+    #[test]
+    fn archived_courses_stay_readable_for_learners() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_course_status(1, CourseStatus::Archived);
+        assert_eq!(contract.get_course_id("learner.testnet".to_string(), 1).id, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Course is not for sale")]
+    fn archived_courses_cannot_be_bought() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_course_status(1, CourseStatus::Archived);
+
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only moderators can review courses")]
+    fn creators_cannot_approve_their_own_course() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_course_status(1, CourseStatus::Archived);
+        contract.approve_course(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Creators cannot approve their own course")]
+    fn moderators_cannot_approve_their_own_course() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_moderator("creator.testnet".to_string());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        let course = contract.publish_course("Draft".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), media("cd"), vec![], U128(100), U128(1_000), None);
+        contract.submit_course(course.id);
+        contract.approve_course(course.id);
+    }
+
+    #[test]
+    fn retired_courses_leave_discovery() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.retire_course(1);
+        assert!(contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, None).items.is_empty());
+        assert_eq!(contract.get_courses_purchased("learner.testnet".to_string(), None, None).items.len(), 1);
+        let view = contract.get_certification_status("learner.testnet".to_string(), 1);
+        assert_eq!(view.status, CertificationStatus::Valid);
+    }
+
+    #[test]
+    fn force_delete_refunds_from_reserve() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("e-learning.testnet", 500, 0));
+        contract.fund_reserve();
+        contract.force_delete_course(1);
+
+        assert_eq!(contract.get_platform_reserve().0, 400);
+        assert!(contract.get_courses_purchased("learner.testnet".to_string(), None, None).items.is_empty());
+        assert!(contract.content_versions.get(&(1, 1)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: platform reserve can't cover refunds : 100")]
+    fn force_delete_requires_funded_reserve() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.force_delete_course(1);
+    }
+
+    #[test]
+    fn category_renames_reach_market_views() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.put_category(1, "Systems programming".to_string(), "img".to_string(), None, 0, "systems".to_string());
+        let market = contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, None).items;
+        assert_eq!(market[0].categories[0].name, "Systems programming");
+    }
+
+    #[test]
+    #[should_panic(expected = "Category does not exist")]
+    fn courses_need_an_existing_category() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(1, "Rust".to_string(), vec![42], vec![], "short".to_string(), "long".to_string(), U128(100), U128(1_000), None);
+    }
+
+    #[test]
+    fn deleting_a_used_category_reassigns_courses() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_category("Web".to_string(), "img".to_string(), None, 0, "web".to_string());
+        contract.delete_category(1, Some(2));
+        assert_eq!(contract.get_course_size(None, Some(2), None), 1);
+        assert_eq!(contract.get_course_size(None, Some(1), None), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Category is still used by courses")]
+    fn deleting_a_used_category_needs_a_target() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.delete_category(1, None);
+    }
+
+    #[test]
+    fn courses_are_indexed_by_categories_and_tags() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_category("Web3".to_string(), "img".to_string(), None, 0, "web3".to_string());
+        assert_eq!(contract.get_market_courses(None, None, None, Some("  SMART   contracts ".to_string()), None, None, None, None, None, None, None).items.len(), 1);
+        assert!(contract.get_market_courses(None, None, Some(2), None, None, None, None, None, None, None, None).items.is_empty());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(1, "Rust".to_string(), vec![1, 2], vec!["near".to_string()], "short".to_string(), "long".to_string(), U128(100), U128(1_000), None);
+        assert_eq!(contract.get_market_courses(None, None, Some(2), Some("near".to_string()), None, None, None, None, None, None, None).items.len(), 1);
+        assert!(contract.get_market_courses(None, None, None, Some("rust".to_string()), None, None, None, None, None, None, None).items.is_empty());
+        assert_eq!(contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, None).items[0].categories.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Courses can have at most 3 categories")]
+    fn category_count_is_limited() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        for name in ["Web", "Design", "Data"] {
+            contract.set_category(name.to_string(), "img".to_string(), None, 0, name.to_string());
+        }
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(1, "Rust".to_string(), vec![1, 2, 3, 4], vec![], "short".to_string(), "long".to_string(), U128(100), U128(1_000), None);
+    }
+
+    #[test]
+    fn parent_categories_include_descendant_courses() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_category("Rust".to_string(), "img".to_string(), Some(1), 1, "rust".to_string());
+        contract.set_category("Smart Contracts".to_string(), "img".to_string(), Some(2), 0, "smart-contracts".to_string());
+        contract.set_category("Go".to_string(), "img".to_string(), Some(1), 0, "go".to_string());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(1, "Rust".to_string(), vec![3], vec![], "short".to_string(), "long".to_string(), U128(100), U128(1_000), None);
+        assert_eq!(contract.get_course_size(None, Some(1), None), 1);
+        assert_eq!(contract.get_course_size(None, Some(2), None), 1);
+        assert_eq!(contract.get_course_size(None, Some(4), None), 0);
+        assert_eq!(contract.get_market_courses(None, None, Some(1), None, None, None, None, None, None, None, None).items.len(), 1);
+
+        let tree = contract.get_category_tree();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].children[0].category.slug, "go");
+        assert_eq!(tree[0].children[1].children[0].category.slug, "smart-contracts");
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Category parent would create a cycle")]
+    fn category_cycles_are_rejected() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_category("Rust".to_string(), "img".to_string(), Some(1), 0, "rust".to_string());
+        contract.put_category(1, "Programming".to_string(), "img".to_string(), Some(2), 0, "programming".to_string());
+    }
+
+    fn publish_and_approve(contract: &mut Contract, title: &str, block_timestamp: u64) -> i128 {
+        testing_env!(get_context("creator.testnet", 0, block_timestamp));
+        let course = contract.publish_course(
+            title.to_string(),
+            vec![1],
+            vec![],
+            "short".to_string(),
+            "long".to_string(),
+            media("cd"),
+            vec![],
+            U128(100),
+            U128(1_000),
+            None,
+        );
+        contract.submit_course(course.id);
+
+        testing_env!(get_context("e-learning.testnet", 0, block_timestamp));
+        contract.approve_course(course.id);
+        course.id
+    }
+
+    fn market_ids(page: &Page<MarketView>) -> Vec<i128> {
+        page.items.iter().map(|x| x.id).collect()
+    }
+
+    #[test]
+    fn market_views_walk_the_indexes() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Solidity", 20);
+        let third = publish_and_approve(&mut contract, "Go", 10);
+
+        // Listings follow publish time, not creation order
+        let first_page = contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, Some(1));
+        let market = contract.get_market_courses(None, None, None, None, None, None, None, None, None, first_page.next_cursor, Some(2)).items;
+        assert_eq!(market.iter().map(|x| x.id).collect::<Vec<i128>>(), vec![third, second]);
+        let recent = contract.get_recent_courses(None, Some(2));
+        assert_eq!(market_ids(&recent), vec![second, third]);
+        assert_eq!(recent.total, 3);
+        let recent = contract.get_recent_courses(recent.next_cursor, Some(2));
+        assert_eq!(market_ids(&recent), vec![1]);
+        assert_eq!(recent.next_cursor, None);
 
-    let refund = attached_deposit - required_cost;
-    if refund > 1 {
-        Promise::new(env::predecessor_account_id()).transfer(refund);
+        assert_eq!(contract.get_course_size(Some("creator.testnet".to_string()), None, None), 3);
+        assert_eq!(contract.get_courses_intructor(Some("creator.testnet".to_string()), Some("1".to_string()), Some(1)).items[0].id, second);
+
+        testing_env!(get_context("creator.testnet", 0, 30));
+        contract.retire_course(second);
+        assert_eq!(contract.get_course_size(None, None, None), 2);
+        assert_eq!(contract.get_course_size(None, Some(1), None), 2);
     }
-}
 
-// unlike the struct's functions above, this function cannot use attributes #[derive(…)] or #[near_bindgen]
-// any attempts will throw helpful warnings upon 'cargo build'
-// while this function cannot be invoked directly on the blockchain, it can be called from an invoked function
+    #[test]
+    fn rating_leaderboard_tracks_reviews() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Solidity", 20);
 
-/*
- * the rest of this file sets up unit tests
- * to run these, the command will be:
- * cargo test --package rust-counter-tutorial -- --nocapture
- * Note: 'rust-counter-tutorial' comes from cargo.toml's 'name' key
- */
+        register(&mut contract, "learner.testnet");
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        contract.course_buy(second);
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.set_review(1, "Good".to_string(), 3);
+        contract.set_review(second, "Great".to_string(), 5);
+        let top = contract.get_courses_rating(None, None);
+        assert_eq!(market_ids(&top), vec![second, 1]);
 
-// use the attribute below for unit tests
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, VMContext};
+        contract.set_review(1, "Excellent after all".to_string(), 5);
+        let top = contract.get_courses_rating(None, Some(1));
+        assert_eq!(top.items[0].id, 1);
+        let top = contract.get_courses_rating(top.next_cursor, Some(1));
+        assert_eq!(top.items[0].id, second);
+        assert_eq!(contract.get_review(1, "learner.testnet".to_string()).unwrap().critics, 5);
+    }
 
-    // part of writing unit tests is setting up a mock context
-    // in this example, this is only needed for env::log in the contract
-    // this is also a useful list to peek at when wondering what's available in env::*
-    fn get_context(input: Vec<u8>, is_view: bool) -> VMContext {
-        VMContext {
-            current_account_id: "alice.testnet".to_string(),
-            signer_account_id: "robert.testnet".to_string(),
-            signer_account_pk: vec![0, 1, 2],
-            predecessor_account_id: "jane.testnet".to_string(),
-            input,
-            block_index: 0,
-            block_timestamp: 0,
-            account_balance: 0,
-            account_locked_balance: 0,
-            storage_usage: 0,
-            attached_deposit: 0,
-            prepaid_gas: 10u64.pow(18),
-            random_seed: vec![0, 1, 2],
-            is_view,
-            output_data_receivers: vec![],
-            epoch_height: 19,
+
+    #[test]
+    fn market_sorts_and_filters() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Solidity", 20);
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(second, "Solidity".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), U128(0), U128(0), None);
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+
+        let page = contract.get_market_courses(None, None, None, None, Some(SortBy::PriceDesc), None, None, None, None, None, None);
+        assert_eq!(market_ids(&page), vec![1, second]);
+        let page = contract.get_market_courses(None, None, None, None, Some(SortBy::Newest), None, None, None, None, None, None);
+        assert_eq!(market_ids(&page), vec![second, 1]);
+        let page = contract.get_market_courses(None, None, None, None, Some(SortBy::Enrollments), None, None, None, None, None, None);
+        assert_eq!(market_ids(&page), vec![1, second]);
+
+        let page = contract.get_market_courses(None, None, None, None, None, Some(U128(50)), Some(U128(150)), None, None, None, None);
+        assert_eq!(market_ids(&page), vec![1]);
+        let page = contract.get_market_courses(None, None, None, None, None, None, None, Some(true), None, None, None);
+        assert_eq!(market_ids(&page), vec![second]);
+        let page = contract.get_market_courses(None, None, None, None, None, None, None, None, Some(true), None, None);
+        assert_eq!(market_ids(&page), vec![1]);
+    }
+
+    #[test]
+    fn market_cursor_is_stable_when_courses_are_added() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Solidity", 20);
+
+        let page = contract.get_market_courses(None, None, None, None, Some(SortBy::PriceAsc), None, None, None, None, None, Some(1));
+        assert_eq!(market_ids(&page), vec![1]);
+
+        // A cheaper course published between page loads lands before the cursor
+        let third = publish_and_approve(&mut contract, "Go", 30);
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(third, "Go".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), U128(10), U128(1_000), None);
+
+        let page = contract.get_market_courses(None, None, None, None, Some(SortBy::PriceAsc), None, None, None, None, page.next_cursor, Some(5));
+        assert_eq!(market_ids(&page), vec![second]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn tokenize_drops_stopwords_and_duplicates() {
+        assert_eq!(
+            tokenize("The Rust book: Rust for the Web, and NEAR smart-contracts"),
+            vec!["rust", "book", "web", "near", "smart", "contracts"],
+        );
+        assert!(tokenize(" , the of ").is_empty());
+    }
+
+    #[test]
+    fn search_ranks_by_matched_terms() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Smart contracts in Rust on NEAR", 20);
+        let third = publish_and_approve(&mut contract, "Solidity smart contracts", 30);
+        let draft = {
+            testing_env!(get_context("creator.testnet", 0, 40));
+            contract.publish_course("Rust smart contracts draft".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), media("cd"), vec![], U128(100), U128(1_000), None).id
+        };
+
+        let page = contract.search_courses("rust smart CONTRACTS".to_string(), None, None);
+        assert_eq!(page.items.iter().map(|x| x.id).collect::<Vec<i128>>(), vec![second, third, 1]);
+        assert!(!page.items.iter().any(|x| x.id == draft));
+        assert_eq!(page.total, 3);
+
+        let results = contract.search_courses("rust smart contracts".to_string(), Some("1".to_string()), Some(1)).items;
+        assert_eq!(results[0].id, third);
+
+        let page = contract.search_courses("rust smart contracts".to_string(), None, Some(2));
+        assert_eq!(page.items.iter().map(|x| x.id).collect::<Vec<i128>>(), vec![second, third]);
+        assert_eq!((page.total, page.next_cursor.clone()), (3, Some("2".to_string())));
+        let page = contract.search_courses("rust smart contracts".to_string(), page.next_cursor, Some(2));
+        assert_eq!(page.items.iter().map(|x| x.id).collect::<Vec<i128>>(), vec![1]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn search_index_follows_course_edits() {
+        let mut contract = setup_contract();
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.put_course(1, "Go concurrency".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), U128(100), U128(1_000), None);
+        assert!(contract.search_courses("rust".to_string(), None, None).items.is_empty());
+        assert_eq!(contract.search_courses("concurrency".to_string(), None, None).items.len(), 1);
+
+        contract.set_course_status(1, CourseStatus::Unlisted);
+        assert_eq!(contract.search_courses("concurrency".to_string(), None, None).total, 0);
+        contract.set_course_status(1, CourseStatus::Published);
+        assert_eq!(contract.search_courses("concurrency".to_string(), None, None).total, 1);
+
+        testing_env!(get_context("e-learning.testnet", 500, 0));
+        contract.fund_reserve();
+        contract.force_delete_course(1);
+        assert!(contract.courses_by_token.get(&"concurrency".to_string()).is_none());
+    }
+
+    #[test]
+    fn list_views_share_the_page_model() {
+        let mut contract = setup_contract();
+        publish_and_approve(&mut contract, "Solidity", 20);
+        publish_and_approve(&mut contract, "Go", 30);
+
+        let page = contract.get_market_courses(None, None, None, None, None, Some(U128(1)), None, None, None, None, Some(2));
+        assert_eq!(page.total, 3);
+        assert_eq!(page.items.len(), 2);
+        let page = contract.get_market_courses(None, None, None, None, None, Some(U128(1)), None, None, None, page.next_cursor, Some(2));
+        assert_eq!(page.items.len(), 1);
+
+        let page = contract.get_courses_intructor(Some("creator.testnet".to_string()), None, Some(2));
+        assert_eq!((page.total, page.next_cursor.clone()), (3, Some("2".to_string())));
+        let page = contract.get_courses_intructor(Some("creator.testnet".to_string()), page.next_cursor, Some(2));
+        assert_eq!((page.items.len(), page.next_cursor), (1, None));
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_moderator("mod1.testnet".to_string());
+        contract.set_moderator("mod2.testnet".to_string());
+        let page = contract.get_moderators(None, Some(1));
+        assert_eq!((page.items, page.next_cursor.clone()), (vec!["mod1.testnet".to_string()], Some("1".to_string())));
+        assert_eq!(contract.get_moderators(page.next_cursor, Some(1)).items, vec!["mod2.testnet".to_string()]);
+
+        let page = contract.get_category(None, None, Some(1));
+        assert_eq!((page.items[0].id, page.total, page.next_cursor), (1, 1, None));
+        assert!(contract.get_category(Some(7), None, None).items.is_empty());
+    }
+
+    #[test]
+    fn recency_survives_removals() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Solidity", 20);
+        let third = publish_and_approve(&mut contract, "Go", 30);
+
+        testing_env!(get_context("creator.testnet", 0, 40));
+        contract.delete_course(1);
+        let recent = contract.get_recent_courses(None, None);
+        assert_eq!(market_ids(&recent), vec![third, second]);
+    }
+
+    #[test]
+    fn activity_timeline_is_newest_first() {
+        let mut contract = setup_contract();
+        register(&mut contract, "learner.testnet");
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 10));
+        contract.course_buy(1);
+        testing_env!(get_context("learner.testnet", 1_000 + STORAGE_DEPOSIT, 20));
+        contract.pass_certification_buy(1);
+        testing_env!(get_context("learner.testnet", 0, 30));
+        contract.set_review(1, "Good".to_string(), 4);
+        testing_env!(get_context("learner.testnet", 0, 40));
+        let review = contract.set_review(1, "Very good".to_string(), 5);
+        assert_eq!((review.created_at, review.edited_at), (30, Some(40)));
+
+        let activity = contract.get_learner_activity("learner.testnet".to_string(), None, None);
+        let kinds: Vec<(ActivityKind, u64)> = activity.items.iter().map(|x| (x.kind.clone(), x.timestamp.0)).collect();
+        assert_eq!(kinds, vec![
+            (ActivityKind::ReviewEdited, 40),
+            (ActivityKind::Reviewed, 30),
+            (ActivityKind::Certified, 20),
+            (ActivityKind::Enrolled, 10),
+        ]);
+    }
+
+    #[test]
+    fn enrollment_history_follows_purchase_time() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "second.testnet", 20);
+        testing_env!(get_context("first.testnet", 100 + STORAGE_DEPOSIT, 10));
+        contract.course_buy(1);
+
+        let history = contract.get_enrollment_history(1, None, None);
+        let entries: Vec<(AccountId, u64, Option<u64>)> = history.items.iter()
+            .map(|x| (x.user_id.to_string(), x.purchased_at.0, x.certified_at.map(|t| t.0)))
+            .collect();
+        assert_eq!(entries, vec![
+            ("first.testnet".to_string(), 10, None),
+            ("second.testnet".to_string(), 20, Some(20)),
+        ]);
+    }
+
+    #[test]
+    fn course_edits_update_timestamp() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.testnet", 0, 50));
+        let course = contract.put_course(1, "Rust 2".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), U128(100), U128(1_000), None);
+        assert!(course.created_at < course.updated_at);
+        assert_eq!(course.updated_at, 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only enrolled learners can review the course")]
+    fn review_requires_enrollment() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.set_review(1, "Good".to_string(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Creators cannot review their own course")]
+    fn creator_cannot_review_own_course() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_review(1, "Good".to_string(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Review score must be between 1 and 5")]
+    fn review_score_is_bounded() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        contract.set_review(1, "Good".to_string(), 127);
+    }
+
+    #[test]
+    #[should_panic(expected = "Review is too long")]
+    fn review_text_is_bounded() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        contract.set_review(1, "a".repeat(MAX_REVIEW_LENGTH + 1), 5);
+    }
+
+    #[test]
+    fn rating_totals_are_exact() {
+        let mut contract = setup_contract();
+        for (learner, score) in [("a.testnet", 5), ("b.testnet", 4), ("c.testnet", 4)] {
+            register(&mut contract, learner);
+            testing_env!(get_context(learner, 100 + STORAGE_DEPOSIT, 0));
+            contract.course_buy(1);
+            contract.set_review(1, "Review".to_string(), score);
+        }
+        testing_env!(get_context("c.testnet", 0, 0));
+        contract.set_review(1, "Changed my mind".to_string(), 1);
+
+        let view = &contract.get_market_courses(Some(1), None, None, None, None, None, None, None, None, None, None).items[0];
+        assert_eq!(view.rating_count, 3);
+        assert_eq!(view.rating_histogram, [1, 0, 0, 1, 1]);
+        assert_eq!(view.rating, 3333);
+    }
+
+    #[test]
+    fn single_review_does_not_top_the_chart() {
+        let mut contract = setup_contract();
+        let second = publish_and_approve(&mut contract, "Solidity", 20);
+        for learner in ["a.testnet", "b.testnet", "c.testnet", "d.testnet", "e.testnet", "f.testnet"] {
+            register(&mut contract, learner);
+            testing_env!(get_context(learner, 100 + STORAGE_DEPOSIT, 0));
+            contract.course_buy(1);
+            contract.set_review(1, "Good".to_string(), 4);
+        }
+        register(&mut contract, "g.testnet");
+        testing_env!(get_context("g.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(second);
+        contract.set_review(second, "Perfect".to_string(), 5);
+
+        let top = contract.get_courses_rating(None, None);
+        assert_eq!(market_ids(&top), vec![1, second]);
+    }
+
+    fn reviewed_course() -> Contract {
+        let mut contract = setup_contract();
+        for (learner, score) in [("troll.testnet", 1), ("learner.testnet", 5)] {
+            register(&mut contract, learner);
+            testing_env!(get_context(learner, 100 + STORAGE_DEPOSIT, 0));
+            contract.course_buy(1);
+            contract.set_review(1, "Review".to_string(), score);
         }
+        contract
+    }
+
+    #[test]
+    fn hidden_reviews_leave_the_aggregates() {
+        let mut contract = reviewed_course();
+        testing_env!(get_context("learner.testnet", 0, 10));
+        contract.report_review(1, "troll.testnet".to_string(), "Spam".to_string());
+        let queue = contract.get_reported_reviews(None, None);
+        assert_eq!(queue.total, 1);
+        assert_eq!(queue.items[0].reports[0].reporter_id, "learner.testnet");
+
+        testing_env!(get_context("e-learning.testnet", 0, 20));
+        contract.hide_review(1, "troll.testnet".to_string());
+        assert!(near_sdk::test_utils::get_logs().iter().any(|x| x.contains("\"event\":\"review_hidden\"")));
+        assert_eq!(contract.get_reported_reviews(None, None).total, 0);
+
+        let view = &contract.get_market_courses(Some(1), None, None, None, None, None, None, None, None, None, None).items[0];
+        assert_eq!((view.rating, view.rating_count), (5000, 1));
+        assert_eq!(contract.get_reviews(1, None, None, None).total, 1);
+
+        contract.restore_review(1, "troll.testnet".to_string());
+        let view = &contract.get_market_courses(Some(1), None, None, None, None, None, None, None, None, None, None).items[0];
+        assert_eq!((view.rating, view.rating_count), (3000, 2));
+        assert_eq!(contract.get_reviews(1, None, None, None).total, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only moderators can review courses")]
+    fn only_moderators_hide_reviews() {
+        let mut contract = reviewed_course();
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.hide_review(1, "troll.testnet".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only enrolled learners can report reviews")]
+    fn report_requires_enrollment() {
+        let mut contract = reviewed_course();
+        testing_env!(get_context("stranger.testnet", 0, 0));
+        contract.report_review(1, "troll.testnet".to_string(), "Spam".to_string());
+    }
+
+    #[test]
+    fn reviews_sort_by_helpfulness() {
+        let mut contract = reviewed_course();
+        register(&mut contract, "voter.testnet");
+        testing_env!(get_context("voter.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        contract.vote_review(1, "troll.testnet".to_string(), false);
+        contract.vote_review(1, "learner.testnet".to_string(), false);
+        let review = contract.vote_review(1, "learner.testnet".to_string(), true);
+        assert_eq!((review.helpful_votes, review.unhelpful_votes), (1, 0));
+
+        let page = contract.get_reviews(1, None, None, Some(ReviewSort::MostHelpful));
+        let reviewers: Vec<AccountId> = page.items.iter().map(|x| x.user_id.to_string()).collect();
+        assert_eq!(reviewers, vec!["learner.testnet", "troll.testnet"]);
+
+        let page = contract.get_reviews(1, None, Some(1), Some(ReviewSort::Oldest));
+        assert_eq!(page.items[0].user_id, "troll.testnet");
+        assert_eq!(page.next_cursor, Some("1".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Review already voted")]
+    fn one_vote_per_account() {
+        let mut contract = reviewed_course();
+        testing_env!(get_context("troll.testnet", 0, 0));
+        contract.vote_review(1, "learner.testnet".to_string(), true);
+        contract.vote_review(1, "learner.testnet".to_string(), true);
+    }
+
+    #[test]
+    fn creator_replies_survive_edits() {
+        let mut contract = reviewed_course();
+        testing_env!(get_context("creator.testnet", 0, 10));
+        contract.reply_review(1, "troll.testnet".to_string(), "Sorry to hear that".to_string());
+        testing_env!(get_context("troll.testnet", 0, 20));
+        let review = contract.set_review(1, "Better now".to_string(), 4);
+        assert_eq!(review.reply.unwrap().replied_at, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "No permission")]
+    fn only_creator_replies() {
+        let mut contract = reviewed_course();
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.reply_review(1, "troll.testnet".to_string(), "Not mine to answer".to_string());
+    }
+
+    #[test]
+    fn reviews_and_enrollments_live_outside_the_course() {
+        let mut contract = reviewed_course();
+        assert!(contract.get_review(1, "stranger.testnet".to_string()).is_none());
+        assert!(contract.get_review(7, "learner.testnet".to_string()).is_none());
+
+        let course = contract.courses.get(&1).unwrap();
+        assert_eq!((course.enrollment_count, course.review_count), (2, 2));
+
+        testing_env!(get_context("e-learning.testnet", 200, 0));
+        contract.fund_reserve();
+        contract.force_delete_course(1);
+        assert!(contract.course_reviews.get(&1).is_none());
+        assert!(contract.course_inscriptions.get(&1).is_none());
+    }
+
+    #[test]
+    fn storage_balance_pays_for_new_data() {
+        let mut contract = setup_contract();
+        let min = contract.storage_balance_bounds().min.0;
+        assert!(min > 0);
+
+        register(&mut contract, "learner.testnet");
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        contract.set_review(1, "Good".to_string(), 4);
+
+        let balance = contract.storage_balance_of(ValidAccountId::try_from("learner.testnet").unwrap()).unwrap();
+        assert_eq!(balance.total.0, STORAGE_DEPOSIT);
+        assert!(balance.available.0 < STORAGE_DEPOSIT - min);
+
+        testing_env!(get_context("learner.testnet", 1, 0));
+        let balance = contract.storage_withdraw(None);
+        assert_eq!(balance.available.0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: storage balance is less than required")]
+    fn storage_balance_must_cover_new_data() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", contract.storage_balance_bounds().min.0, 0));
+        contract.storage_deposit(None, Some(true));
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.set_review(1, "Good".to_string(), 4);
+    }
+
+    #[test]
+    fn unregister_returns_the_deposit() {
+        let mut contract = setup_contract();
+        register(&mut contract, "learner.testnet");
+        testing_env!(get_context("learner.testnet", 1, 0));
+        assert!(contract.storage_unregister(None));
+        assert!(!contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(ValidAccountId::try_from("learner.testnet").unwrap()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister an account that still stores data")]
+    fn unregister_keeps_paid_data() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.testnet", 1, 0));
+        contract.storage_unregister(None);
     }
 
-    // mark individual unit tests with #[test] for them to be registered and fired
     #[test]
-    fn increment() {
-        // set up the mock context into the testing environment
-        let context = get_context(vec![], false);
+    fn storage_is_charged_to_the_predecessor() {
+        let mut contract = setup_contract();
+        register(&mut contract, "relay.testnet");
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+
+        let mut context = get_context("learner.testnet", 0, 0);
+        context.predecessor_account_id = "relay.testnet".to_string();
         testing_env!(context);
-        // instantiate a contract variable with the counter at zero
-        let mut contract = Counter { val: 0 };
-        contract.increment();
-        println!("Value after increment: {}", contract.get_num());
-        // confirm that we received 1 when calling get_num
-        assert_eq!(1, contract.get_num());
+        contract.set_review(1, "Good".to_string(), 4);
+
+        let relay = contract.storage_balance_of(ValidAccountId::try_from("relay.testnet").unwrap()).unwrap();
+        assert!(relay.available.0 < STORAGE_DEPOSIT - contract.storage_balance_bounds().min.0);
+        assert!(contract.storage_balance_of(ValidAccountId::try_from("learner.testnet").unwrap()).is_none());
+    }
+
+    fn storage_total(contract: &Contract, account_id: &str) -> Balance {
+        contract.storage_balance_of(ValidAccountId::try_from(account_id).unwrap()).map(|x| x.total.0).unwrap_or_default()
+    }
+
+    fn storage_used(contract: &Contract, account_id: &str) -> Balance {
+        let balance = contract.storage_balance_of(ValidAccountId::try_from(account_id).unwrap()).unwrap();
+        balance.total.0 - balance.available.0
+    }
+
+    #[test]
+    fn force_delete_releases_storage_to_each_payer() {
+        let mut contract = reviewed_course();
+        register(&mut contract, "keys.testnet");
+        let min = contract.storage_balance_bounds().min.0;
+        let creator_before = storage_used(&contract, "creator.testnet");
+        let troll_before = storage_used(&contract, "troll.testnet");
+
+        testing_env!(get_context("learner.testnet", 0, 0));
+        let before_key = storage_used(&contract, "learner.testnet");
+        contract.register_learner_key("ed25519:learner".to_string());
+        let key_cost = storage_used(&contract, "learner.testnet") - before_key;
+        contract.vote_review(1, "troll.testnet".to_string(), false);
+        contract.report_review(1, "troll.testnet".to_string(), "Spam".to_string());
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.reply_review(1, "learner.testnet".to_string(), "Thanks".to_string());
+        contract.set_course_key_service(1, Some("keys.testnet".to_string()));
+        testing_env!(get_context("keys.testnet", 0, 0));
+        contract.set_key_envelopes(1, vec![KeyEnvelopeInput {
+            user_id: "learner.testnet".to_string(),
+            encrypted_key: "sealed".to_string(),
+        }]);
+
+        testing_env!(get_context("e-learning.testnet", 200, 0));
+        contract.fund_reserve();
+        contract.force_delete_course(1);
+
+        // The learner keeps paying for the registered key, which outlives the course
+        assert_eq!(storage_used(&contract, "learner.testnet"), min + key_cost);
+        assert_eq!(storage_used(&contract, "keys.testnet"), min);
+        assert!(storage_used(&contract, "troll.testnet") < troll_before);
+        assert!(storage_used(&contract, "creator.testnet") < creator_before);
     }
 
     #[test]
-    fn decrement() {
-        let context = get_context(vec![], false);
+    fn force_delete_releases_relayed_storage_to_the_relay() {
+        let mut contract = setup_contract();
+        register(&mut contract, "relay.testnet");
+        register(&mut contract, "troll.testnet");
+        let min = contract.storage_balance_bounds().min.0;
+        testing_env!(get_context("troll.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        contract.set_review(1, "Review".to_string(), 1);
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        let troll_before = storage_used(&contract, "troll.testnet");
+        let creator_before = storage_used(&contract, "creator.testnet");
+
+        let relayed = |signer| {
+            let mut context = get_context(signer, 0, 0);
+            context.predecessor_account_id = "relay.testnet".to_string();
+            context
+        };
+        testing_env!(relayed("learner.testnet"));
+        contract.set_review(1, "Good".to_string(), 4);
+        contract.vote_review(1, "troll.testnet".to_string(), false);
+        contract.report_review(1, "troll.testnet".to_string(), "Spam".to_string());
+        testing_env!(relayed("creator.testnet"));
+        contract.reply_review(1, "troll.testnet".to_string(), "Thanks".to_string());
+        assert!(storage_used(&contract, "relay.testnet") > min);
+
+        testing_env!(get_context("e-learning.testnet", 200, 0));
+        contract.fund_reserve();
+        contract.force_delete_course(1);
+
+        assert_eq!(storage_used(&contract, "relay.testnet"), min);
+        assert!(storage_used(&contract, "troll.testnet") < troll_before);
+        assert!(storage_used(&contract, "creator.testnet") < creator_before);
+        assert!(contract.storage_balance_of(ValidAccountId::try_from("learner.testnet").unwrap()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Review must be edited from the account that paid for it")]
+    fn relayed_reviews_are_edited_through_the_same_payer() {
+        let mut contract = reviewed_course();
+        let mut context = get_context("learner.testnet", STORAGE_DEPOSIT, 0);
+        context.predecessor_account_id = "relay.testnet".to_string();
         testing_env!(context);
-        let mut contract = Counter { val: 0 };
-        contract.decrement();
-        println!("Value after decrement: {}", contract.get_num());
-        // confirm that we received -1 when calling get_num
-        assert_eq!(-1, contract.get_num());
+        contract.set_review(1, "Edited".to_string(), 4);
+    }
+
+    #[test]
+    fn hiding_a_review_releases_report_storage() {
+        let mut contract = reviewed_course();
+        let before = storage_used(&contract, "learner.testnet");
+
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.report_review(1, "troll.testnet".to_string(), "Spam".to_string());
+        assert!(storage_used(&contract, "learner.testnet") > before);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.hide_review(1, "troll.testnet".to_string());
+        assert_eq!(storage_used(&contract, "learner.testnet"), before);
+    }
+
+    #[test]
+    fn replaced_key_envelopes_release_the_previous_payer() {
+        let mut contract = setup_contract();
+        buy_and_certify(&mut contract, "learner.testnet", 0);
+        register(&mut contract, "learner.testnet");
+        register(&mut contract, "keys.testnet");
+        testing_env!(get_context("learner.testnet", 0, 0));
+        contract.register_learner_key("ed25519:learner".to_string());
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.set_course_key_service(1, Some("keys.testnet".to_string()));
+        let creator_before = storage_used(&contract, "creator.testnet");
+        let envelope = vec![KeyEnvelopeInput {
+            user_id: "learner.testnet".to_string(),
+            encrypted_key: "sealed".to_string(),
+        }];
+        contract.set_key_envelopes(1, envelope.clone());
+        assert!(storage_used(&contract, "creator.testnet") > creator_before);
+
+        testing_env!(get_context("keys.testnet", 0, 0));
+        contract.set_key_envelopes(1, envelope);
+        assert_eq!(storage_used(&contract, "creator.testnet"), creator_before);
+        assert_eq!(contract.get_key_envelope("learner.testnet".to_string(), 1).payer_id, "keys.testnet");
+    }
+
+    #[test]
+    fn attached_deposit_pays_for_a_new_course() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("newcomer.testnet", STORAGE_DEPOSIT, 0));
+        contract.publish_course(
+            "Go".to_string(),
+            vec![1],
+            vec![],
+            "short".to_string(),
+            "long".to_string(),
+            media("ab"),
+            vec![],
+            U128(100),
+            U128(1_000),
+            None,
+        );
+
+        // Only the bytes the course uses are kept, the rest of the deposit goes back
+        let account = contract.storage_accounts.get(&"newcomer.testnet".to_string()).unwrap();
+        assert_eq!(account.deposit, storage_cost(account.used_bytes));
+        assert!(account.used_bytes > contract.account_storage_usage);
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: storage balance is less than required")]
+    fn review_deposit_must_cover_storage() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        testing_env!(get_context("learner.testnet", 100, 0));
+        contract.set_review(1, "Good".to_string(), 4);
+    }
+
+    #[test]
+    fn freed_storage_is_refunded() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.testnet", 0, 0));
+        let draft = contract.publish_course("Draft".to_string(), vec![1], vec![], "short".to_string(), "long".to_string(), media("cd"), vec![], U128(100), U128(1_000), None).id;
+        contract.add_section(draft, "A long section title that takes some bytes".to_string());
+        let before = storage_total(&contract, "creator.testnet");
+
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.delete_section(draft, 0);
+        assert!(storage_total(&contract, "creator.testnet") < before);
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        let category = contract.set_category("Design".to_string(), "img".to_string(), None, 1, "design".to_string());
+        let before = storage_total(&contract, "e-learning.testnet");
+        contract.delete_category(category.id, None);
+        assert!(storage_total(&contract, "e-learning.testnet") < before);
+    }
+
+    #[test]
+    fn purchases_never_decrease_contract_balance() {
+        let mut contract = setup_contract();
+        // The mocked balance starts as account_balance + attached_deposit and drops with every transfer
+        let initial_balance = 10u128.pow(26);
+
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        assert!(env::account_balance() > initial_balance);
+        assert!(env::account_balance() < initial_balance + STORAGE_DEPOSIT);
+
+        testing_env!(get_context("learner.testnet", 1_000 + STORAGE_DEPOSIT, 0));
+        contract.pass_certification_buy(1);
+        assert!(env::account_balance() > initial_balance);
+
+        testing_env!(get_context("learner.testnet", 700, 0));
+        contract.renew_certification(1);
+        assert!(env::account_balance() >= initial_balance);
+    }
+
+    fn receivers() -> Vec<String> {
+        near_sdk::test_utils::get_created_receipts().iter()
+            .map(|x| format!("{:?}", x).split("receiver_id: \"").nth(1).unwrap().split('"').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn purchase_storage_is_refunded_to_the_enrolled_signer() {
+        let mut contract = setup_contract();
+        let relayed = |deposit| {
+            let mut context = get_context("learner.testnet", deposit, 0);
+            context.predecessor_account_id = "relay.testnet".to_string();
+            context
+        };
+
+        testing_env!(relayed(100 + STORAGE_DEPOSIT));
+        contract.course_buy(1);
+        assert!(contract.is_enrolled(1, &"learner.testnet".to_string()));
+        assert_eq!(receivers(), vec!["creator.testnet", "vault.testnet", "learner.testnet"]);
+
+        testing_env!(relayed(1_000 + STORAGE_DEPOSIT));
+        contract.pass_certification_buy(1);
+        assert_eq!(receivers(), vec!["creator.testnet", "vault.testnet", "learner.testnet"]);
+
+        testing_env!(relayed(800));
+        contract.renew_certification(1);
+        assert_eq!(receivers(), vec!["creator.testnet", "vault.testnet", "learner.testnet"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn purchase_deposit_covers_new_profile() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", 100, 0));
+        contract.course_buy(1);
+    }
+
+    #[test]
+    fn paused_scopes_block_only_their_methods() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        let status = contract.pause(PauseScope::Purchases);
+        assert!(status.purchases && !status.all);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|x| x.contains("\"event\":\"paused\"")));
+
+        // Views and other scopes keep working
+        assert_eq!(contract.get_market_courses(None, None, None, None, None, None, None, None, None, None, None).total, 1);
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.add_section(1, "Intro".to_string());
+
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        let status = contract.unpause(PauseScope::Purchases);
+        assert!(!status.purchases);
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Purchases is paused")]
+    fn paused_purchases_fail() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.pause(PauseScope::Purchases);
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
     }
 
     #[test]
-    fn increment_and_reset() {
-        let context = get_context(vec![], false);
+    #[should_panic(expected = "Artemis: Reviews is paused")]
+    fn pausing_all_stops_every_scope() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("learner.testnet", 100 + STORAGE_DEPOSIT, 0));
+        contract.course_buy(1);
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.pause(PauseScope::All);
+        testing_env!(get_context("learner.testnet", STORAGE_DEPOSIT, 0));
+        contract.set_review(1, "Good".to_string(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner or administrators can pause the contract")]
+    fn only_owner_or_admin_pauses() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("creator.testnet", 0, 0));
+        contract.pause(PauseScope::All);
+    }
+
+    const UPGRADE_CODE: &[u8] = b"new contract code";
+
+    #[test]
+    fn upgrades_deploy_after_the_timelock() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 10));
+        let pending = contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+        assert_eq!(pending.available_at.0, 10 + DEFAULT_UPGRADE_DELAY_NS);
+
+        // Anyone can inspect the pending upgrade during the delay
+        testing_env!(get_context("learner.testnet", 0, 20));
+        assert_eq!(contract.get_pending_upgrade().unwrap().code_hash, pending.code_hash);
+
+        testing_env!(get_context("e-learning.testnet", 0, 10 + DEFAULT_UPGRADE_DELAY_NS));
+        contract.deploy_upgrade(UPGRADE_CODE.to_vec());
+        assert!(contract.get_pending_upgrade().is_none());
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Upgrade is timelocked")]
+    fn upgrades_wait_for_the_delay() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 10));
+        contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+        contract.deploy_upgrade(UPGRADE_CODE.to_vec());
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Code does not match the staged hash")]
+    fn upgrades_must_match_the_staged_hash() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+        testing_env!(get_context("e-learning.testnet", 0, DEFAULT_UPGRADE_DELAY_NS));
+        contract.deploy_upgrade(b"other code".to_vec());
+    }
+
+    #[test]
+    #[should_panic(expected = "Artemis: Upgrade is timelocked")]
+    fn shortened_delay_cannot_deploy_early() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 10));
+        contract.set_upgrade_delay(U64(0));
+        assert_eq!(contract.get_upgrade_delay().0, DEFAULT_UPGRADE_DELAY_NS);
+        assert_eq!(contract.get_pending_upgrade_delay().unwrap().available_at.0, 10 + DEFAULT_UPGRADE_DELAY_NS);
+
+        contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+        testing_env!(get_context("e-learning.testnet", 0, 11));
+        contract.deploy_upgrade(UPGRADE_CODE.to_vec());
+    }
+
+    #[test]
+    fn shortened_delay_applies_after_the_current_delay() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("e-learning.testnet", 0, 0));
+        contract.set_upgrade_delay(U64(2 * DEFAULT_UPGRADE_DELAY_NS));
+        assert_eq!(contract.get_upgrade_delay().0, 2 * DEFAULT_UPGRADE_DELAY_NS);
+
+        contract.set_upgrade_delay(U64(10));
+        testing_env!(get_context("e-learning.testnet", 0, 2 * DEFAULT_UPGRADE_DELAY_NS));
+        assert_eq!(contract.get_upgrade_delay().0, 10);
+        assert!(contract.get_pending_upgrade_delay().is_none());
+
+        let pending = contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+        assert_eq!(pending.available_at.0, 2 * DEFAULT_UPGRADE_DELAY_NS + 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can manage upgrades")]
+    fn only_owner_stages_upgrades() {
+        let mut contract = setup_contract();
+        testing_env!(get_context("juanochando.testnet", 0, 0));
+        contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can manage upgrades")]
+    fn upgrades_are_not_staged_through_other_contracts() {
+        let mut contract = setup_contract();
+        let mut context = get_context("e-learning.testnet", 0, 0);
+        context.predecessor_account_id = "other.testnet".to_string();
         testing_env!(context);
-        let mut contract = Counter { val: 0 };
-        contract.increment();
-        contract.reset();
-        println!("Value after reset: {}", contract.get_num());
-        // confirm that we received -1 when calling get_num
-        assert_eq!(0, contract.get_num());
-    }
-}
\ No newline at end of file
+        contract.stage_upgrade(to_hex(&env::sha256(UPGRADE_CODE)));
+    }
+}